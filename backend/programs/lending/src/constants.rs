@@ -1,8 +1,12 @@
 use anchor_lang::prelude::*;
 
 #[constant]
-// https://pyth.network/developers/price-feed-ids#solana-stable
-pub const SOL_USD_FEED_ID: &str = "0xef0d8b6fda2ceba41da15d4095d1da392a0d2f8ed0c6c7bc0f4cfac8c280b56d";
-pub const USDC_USD_FEED_ID: &str = "0xeaa020c61cc479712813461ce153894a96a6c00b21ed0cfc2798d1f9a9e9c94a";
-pub const MAXIMUM_AGE: u64 = 100; // allow price feed 100 sec old, to avoid stale price feed errors
-
+pub const MAX_USER_POSITIONS: usize = 8; // number of banks a single user can hold positions in
+pub const BPS_SCALE: u64 = 10_000; // 100% expressed in basis points
+pub const SECONDS_PER_YEAR: u64 = 31_536_000; // 365 days, used to convert annual rates to per-second accrual
+pub const VIRTUAL_SHARES: u64 = 1_000_000; // shares every bank starts with that no user owns
+pub const VIRTUAL_ASSETS: u64 = 1_000_000; // tokens backing the virtual shares, keeps the initial exchange rate at 1:1
+pub const MAX_LIQUIDATION_BONUS: u64 = 2_500; // 25%, caps the share of seized collateral paid to liquidators
+pub const MAX_ALLOWED_MINT_EXTENSIONS: usize = 16; // Token-2022 mint extensions the protocol config can allowlist
+pub const DEBT_CEILING_DECIMALS: u32 = 2; // isolated debt is counted in USD cents at the borrowed asset's price
+pub const MAX_EMODE_CATEGORIES: usize = 8; // e-mode categories the protocol config can hold
@@ -13,5 +13,75 @@ pub enum ErrorCode {
     #[msg("Attempting to borrow more than allowed.")]
     OverBorrowableAmount,
     #[msg("User is not undercollateralized.")]
-    NotUndercollateralized
+    NotUndercollateralized,
+    #[msg("User has no position in this bank.")]
+    PositionNotFound,
+    #[msg("User has reached the maximum number of positions.")]
+    MaxPositionsReached,
+    #[msg("Invalid oracle configuration.")]
+    InvalidOracleConfig,
+    #[msg("Invalid interest rate configuration.")]
+    InvalidInterestRateConfig,
+    #[msg("Math operation overflowed.")]
+    MathOverflow,
+    #[msg("Amount is too small to be represented in shares.")]
+    ZeroShares,
+    #[msg("Missing or mismatched bank and oracle accounts for the user's positions.")]
+    InvalidRemainingAccounts,
+    #[msg("Repay amount exceeds the close factor of the borrower's debt.")]
+    CloseFactorExceeded,
+    #[msg("Borrower does not have enough collateral to cover the repay amount and bonus.")]
+    InsufficientCollateral,
+    #[msg("Liquidation repay amount must be greater than zero.")]
+    ZeroLiquidationAmount,
+    #[msg("Invalid bank configuration.")]
+    InvalidBankConfig,
+    #[msg("Signer is not authorized to perform this action.")]
+    Unauthorized,
+    #[msg("This operation is currently paused.")]
+    OperationPaused,
+    #[msg("Deposit would exceed the bank's deposit cap.")]
+    DepositCapExceeded,
+    #[msg("Borrow would exceed the bank's borrow cap.")]
+    BorrowCapExceeded,
+    #[msg("A flash loan is in progress on this bank.")]
+    FlashLoanInProgress,
+    #[msg("Flash borrow has no matching flash repay later in the transaction.")]
+    MissingFlashRepay,
+    #[msg("Flash loans cannot be taken through a CPI.")]
+    FlashLoanCpi,
+    #[msg("No flash loan is in progress on this bank.")]
+    NoFlashLoan,
+    #[msg("Oracle price must be positive.")]
+    InvalidPrice,
+    #[msg("Oracle price confidence interval is too wide.")]
+    PriceConfidenceTooWide,
+    #[msg("Price account does not match the bank's oracle configuration.")]
+    InvalidOracleAccount,
+    #[msg("Oracle price is stale.")]
+    StalePrice,
+    #[msg("Both the primary and fallback oracle prices are stale.")]
+    OraclesStale,
+    #[msg("Primary and fallback oracle prices deviate too much.")]
+    OracleDeviationExceeded,
+    #[msg("Mint has a Token-2022 extension that is not on the protocol's allowlist.")]
+    MintExtensionNotAllowed,
+    #[msg("Invalid mint extension allowlist.")]
+    InvalidMintExtensionAllowlist,
+    #[msg("Isolated collateral cannot be combined with other collateral.")]
+    IsolationModeViolation,
+    #[msg("Asset cannot be borrowed against isolated collateral.")]
+    NotBorrowableInIsolation,
+    #[msg("Borrow would exceed the isolated collateral's debt ceiling.")]
+    DebtCeilingExceeded,
+    #[msg("Invalid e-mode category.")]
+    InvalidEModeCategory,
+    #[msg("User still has deposits that can repay their debt.")]
+    UserHasDeposits,
+    #[msg("User has no debt in this bank.")]
+    NoBadDebt,
+    #[msg("Collateral and borrowed banks must be different.")]
+    SameLiquidationBank,
+    #[msg("Flash loan repayment received is less than the amount borrowed.")]
+    FlashRepayShortfall,
 }
\ No newline at end of file
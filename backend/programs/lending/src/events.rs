@@ -0,0 +1,255 @@
+use anchor_lang::prelude::*;
+use crate::state::{BankConfig, EModeCategory, OracleConfig, PauseFlags};
+
+// Prices and health factors are fixed-point values scaled by `WAD`
+
+#[event]
+pub struct BankInitialized {
+    pub bank: Pubkey,
+    pub mint: Pubkey,
+    pub authority: Pubkey,
+    pub config: BankConfig,
+    pub oracle: OracleConfig,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BankConfigUpdated {
+    pub bank: Pubkey,
+    pub old_config: BankConfig,
+    pub new_config: BankConfig,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BankOracleUpdated {
+    pub bank: Pubkey,
+    pub old_oracle: OracleConfig,
+    pub new_oracle: OracleConfig,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BankFallbackOracleUpdated {
+    pub bank: Pubkey,
+    pub fallback_oracle: Option<OracleConfig>,
+    pub max_oracle_deviation: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BankPauseSet {
+    pub bank: Pubkey,
+    pub paused: PauseFlags,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct InterestAccrued {
+    pub bank: Pubkey,
+    /// Interest charged to borrowers
+    pub borrow_interest: u64,
+    /// Part of the interest credited to depositors
+    pub deposit_interest: u64,
+    /// Part of the interest added to the bank's protocol fees
+    pub protocol_fee: u64,
+    pub total_deposits: u64,
+    pub total_borrowed: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct DepositEvent {
+    pub user: Pubkey,
+    pub bank: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+    pub total_deposits: u64,
+    pub total_deposit_shares: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct WithdrawEvent {
+    pub user: Pubkey,
+    pub bank: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+    pub total_deposits: u64,
+    pub total_deposit_shares: u64,
+    /// Price of the withdrawn asset, `None` when the withdrawal closed the position
+    pub price: Option<u128>,
+    pub health_factor: u128,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BorrowEvent {
+    pub user: Pubkey,
+    pub bank: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+    pub total_borrowed: u64,
+    pub total_borrowed_shares: u64,
+    /// Origination fee added to the user's debt on top of `amount`
+    pub fee: u64,
+    pub price: u128,
+    pub health_factor: u128,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RepayEvent {
+    pub user: Pubkey,
+    pub bank: Pubkey,
+    pub amount: u64,
+    pub shares: u64,
+    pub total_borrowed: u64,
+    pub total_borrowed_shares: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct LiquidationEvent {
+    pub liquidator: Pubkey,
+    pub borrower: Pubkey,
+    pub collateral_bank: Pubkey,
+    pub borrowed_bank: Pubkey,
+    pub repay_amount: u64,
+    pub repaid_shares: u64,
+    pub seized_amount: u64,
+    pub seized_shares: u64,
+    /// Bonus applied to the seized collateral, in basis points
+    pub liquidation_bonus: u64,
+    /// Part of `seized_amount` kept by the protocol instead of going to the liquidator
+    pub protocol_fee: u64,
+    pub collateral_price: u128,
+    pub borrowed_price: u128,
+    pub health_factor_before: u128,
+    pub health_factor_after: u128,
+    pub collateral_total_deposits: u64,
+    pub borrowed_total_borrowed: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FlashBorrowEvent {
+    pub user: Pubkey,
+    pub bank: Pubkey,
+    pub amount: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FlashRepayEvent {
+    pub user: Pubkey,
+    pub bank: Pubkey,
+    pub amount: u64,
+    pub fee: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeesCollected {
+    pub bank: Pubkey,
+    pub fee_recipient: Pubkey,
+    pub amount: u64,
+    /// Fees left in the bank because the treasury did not hold enough idle liquidity
+    pub remaining_fees: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct BadDebtWrittenOff {
+    pub user: Pubkey,
+    pub bank: Pubkey,
+    /// Debt written off, including accrued interest
+    pub amount: u64,
+    pub shares: u64,
+    /// Part of `amount` absorbed by the bank's protocol fees
+    pub covered_by_reserves: u64,
+    /// Part of `amount` taken out of `total_deposits` and shared by all depositors
+    pub socialized: u64,
+    pub total_deposits: u64,
+    pub total_borrowed: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CollateralToggled {
+    pub user: Pubkey,
+    pub bank: Pubkey,
+    pub is_collateral: bool,
+    pub health_factor: u128,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct UserHealthUpdated {
+    pub user: Pubkey,
+    pub health_factor: u128,
+    pub liquidatable_since: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct UserEModeSet {
+    pub user: Pubkey,
+    pub emode_category: u8,
+    pub health_factor: u128,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct UserInitialized {
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ProtocolConfigInitialized {
+    pub admin: Pubkey,
+    pub guardian: Pubkey,
+    pub fee_recipient: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ProtocolRolesUpdated {
+    pub guardian: Pubkey,
+    pub fee_recipient: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminProposed {
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AdminAccepted {
+    pub old_admin: Pubkey,
+    pub new_admin: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct MintExtensionsAllowed {
+    pub extensions: Vec<u16>,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct EModeCategorySet {
+    pub category: EModeCategory,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct ProtocolPauseSet {
+    pub paused: PauseFlags,
+    pub timestamp: i64,
+}
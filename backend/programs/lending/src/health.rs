@@ -0,0 +1,284 @@
+use anchor_lang::prelude::*;
+use crate::constants::BPS_SCALE;
+use crate::error::ErrorCode;
+use crate::interest::accrue_interest;
+use crate::math::{Decimal, Rounding};
+use crate::oracle::{load_oracle_price, load_price, OraclePrice, PriceKind};
+use crate::state::{Bank, EModeCategory, ProtocolConfig, User};
+
+/// Which collateral weight a health check applies
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HealthType {
+    /// Weights collateral by `max_ltv`, used before opening new debt or removing collateral.
+    /// Banks with `use_ema` set are priced with the EMA.
+    Initial,
+    /// Weights collateral by `liquidation_threshold`, used to decide whether a user can be liquidated.
+    /// Always priced with the spot price.
+    Maintenance,
+}
+
+pub struct Health {
+    /// Value of the user's deposits enabled as collateral, weighted by the collateral factor of each bank
+    pub weighted_collateral: Decimal,
+    /// Value of the user's debt
+    pub debt: Decimal,
+    /// Price of one base unit of each bank's asset the user has a position in
+    pub prices: Vec<(Pubkey, OraclePrice)>,
+    /// E-mode category whose parameters applied to the user's positions in its banks
+    pub emode: Option<EModeCategory>,
+}
+
+impl Health {
+    /// Weighted collateral divided by debt. Below one the user is undercollateralized.
+    pub fn factor(&self) -> Result<Decimal> {
+        if self.debt.is_zero() {
+            return Ok(Decimal::max_value());
+        }
+        self.weighted_collateral.try_div(self.debt, Rounding::Down)
+    }
+
+    pub fn is_healthy(&self) -> bool {
+        self.weighted_collateral >= self.debt
+    }
+
+    /// Mid price the health check read for `bank`
+    pub fn price(&self, bank: &Pubkey) -> Result<Decimal> {
+        self.prices
+            .iter()
+            .find(|(key, _)| key == bank)
+            .map(|(_, price)| price.price)
+            .ok_or_else(|| ErrorCode::InvalidRemainingAccounts.into())
+    }
+
+    /// Liquidation bonus in basis points paid out of `bank`'s collateral.
+    ///
+    /// The bonus equals the user's shortfall below a health factor of one, bounded by the bank's minimum and
+    /// maximum bonus (the e-mode maximum if the category applied to the bank). Banks with a
+    /// `liquidation_bonus_duration` also raise it linearly to the maximum over the time the user has been
+    /// liquidatable for, like a Dutch auction.
+    pub fn liquidation_bonus(&self, bank: &Bank, liquidatable_for: i64) -> Result<u64> {
+        let max_bonus = match self.emode {
+            Some(category) if category.id == bank.emode_category => category.liquidation_bonus,
+            _ => bank.liquidation_bonus,
+        };
+        let min_bonus = bank.min_liquidation_bonus.min(max_bonus);
+
+        let factor = self.factor()?;
+        let shortfall = if factor < Decimal::one() {
+            Decimal::one()
+                .try_sub(factor)?
+                .try_mul(Decimal::from(BPS_SCALE), Rounding::Down)?
+                .to_u64(Rounding::Down)?
+        } else {
+            0
+        };
+        let mut bonus = shortfall.clamp(min_bonus, max_bonus);
+
+        if bank.liquidation_bonus_duration > 0 {
+            let elapsed = (liquidatable_for.max(0) as u64).min(bank.liquidation_bonus_duration);
+            let time_bonus = Decimal::from(max_bonus - min_bonus)
+                .try_mul(Decimal::from_ratio(elapsed, bank.liquidation_bonus_duration, Rounding::Down)?, Rounding::Down)?
+                .to_u64(Rounding::Down)?;
+            bonus = bonus.max(min_bonus + time_bonus);
+        }
+
+        Ok(bonus)
+    }
+}
+
+/// Values every open position of `user` through its bank's oracle.
+///
+/// Deposits are valued at the bottom of the oracle's confidence band and debt at the top, so an uncertain
+/// price can only make the user look less healthy.
+///
+/// `remaining_accounts` must hold a `[bank, price account]` pair for each non-empty position, in any order,
+/// with the fallback price account right after the pair of banks that configure a fallback oracle.
+/// Price accounts are the bank's Pyth or Switchboard feed; banks with a fixed price accept any account.
+/// If the user's e-mode category has a shared oracle, its price account comes last.
+/// Banks the instruction has already loaded and modified are passed in `loaded_banks` so the health check
+/// sees their updated state; their pair must still be present for the price account.
+///
+/// The user's e-mode category only applies while every one of their borrows is in a bank of that category.
+pub fn compute_health<'a>(
+    user: &User,
+    protocol_config: &ProtocolConfig,
+    loaded_banks: &[(Pubkey, &Bank)],
+    remaining_accounts: &'a [AccountInfo<'a>],
+    health_type: HealthType,
+) -> Result<Health> {
+    let category = protocol_config.emode_category(user.emode_category);
+    let (remaining_accounts, emode_price_info) = match category.and_then(|category| category.oracle) {
+        Some(_) => {
+            let (emode_price_info, remaining_accounts) = remaining_accounts.split_last().ok_or(ErrorCode::InvalidRemainingAccounts)?;
+            (remaining_accounts, Some(emode_price_info))
+        }
+        None => (remaining_accounts, None),
+    };
+    let banks = load_banks(loaded_banks, remaining_accounts)?;
+
+    let mut emode = category.copied();
+    for position in user.positions.iter().filter(|position| position.borrowed_shares > 0) {
+        let (_, bank, ..) = find_bank(&banks, &position.bank)?;
+        if emode.is_some_and(|category| category.id != bank.emode_category) {
+            emode = None;
+        }
+    }
+
+    let mut weighted_collateral = Decimal::zero();
+    let mut debt = Decimal::zero();
+    let mut prices = Vec::with_capacity(user.positions.len());
+
+    for position in user.positions.iter().filter(|position| !position.is_empty()) {
+        let (_, bank, price_info, fallback_price_info) = find_bank(&banks, &position.bank)?;
+        let bank_emode = emode.filter(|category| category.id == bank.emode_category);
+
+        let price = match bank_emode.and_then(|category| category.oracle) {
+            Some(oracle) => {
+                let emode_price_info = emode_price_info.ok_or(ErrorCode::InvalidRemainingAccounts)?;
+                load_oracle_price(&oracle, bank.decimals, emode_price_info, price_kind(health_type, oracle.use_ema))?
+            }
+            None => load_price(bank, price_info, *fallback_price_info, price_kind(health_type, bank.oracle.use_ema))?,
+        };
+        prices.push((position.bank, price));
+
+        if position.deposited_shares > 0 && position.is_collateral {
+            let weight = match (health_type, bank_emode) {
+                (HealthType::Initial, Some(category)) => category.max_ltv,
+                (HealthType::Initial, None) => bank.max_ltv,
+                (HealthType::Maintenance, Some(category)) => category.liquidation_threshold,
+                (HealthType::Maintenance, None) => bank.liquidation_threshold,
+            };
+            let deposited = bank.deposit_amount(position.deposited_shares, Rounding::Down)?;
+            let value = price
+                .lower()?
+                .try_mul(Decimal::from(deposited), Rounding::Down)?
+                .try_mul(Decimal::from_bps(weight), Rounding::Down)?;
+            weighted_collateral = weighted_collateral.try_add(value)?;
+        }
+
+        if position.borrowed_shares > 0 {
+            let borrowed = bank.borrow_amount(position.borrowed_shares, Rounding::Up)?;
+            let value = price.upper()?.try_mul(Decimal::from(borrowed), Rounding::Up)?;
+            debt = debt.try_add(value)?;
+        }
+    }
+
+    Ok(Health { weighted_collateral, debt, prices, emode })
+}
+
+fn price_kind(health_type: HealthType, use_ema: bool) -> PriceKind {
+    match health_type {
+        HealthType::Initial if use_ema => PriceKind::Ema,
+        _ => PriceKind::Spot,
+    }
+}
+
+type BankAccounts<'a> = (Pubkey, Bank, &'a AccountInfo<'a>, Option<&'a AccountInfo<'a>>);
+
+fn find_bank<'b, 'a>(banks: &'b [BankAccounts<'a>], bank: &Pubkey) -> Result<&'b BankAccounts<'a>> {
+    banks
+        .iter()
+        .find(|(key, ..)| key == bank)
+        .ok_or_else(|| ErrorCode::InvalidRemainingAccounts.into())
+}
+
+/// Splits `remaining_accounts` into each bank with its price accounts
+fn load_banks<'a>(loaded_banks: &[(Pubkey, &Bank)], remaining_accounts: &'a [AccountInfo<'a>]) -> Result<Vec<BankAccounts<'a>>> {
+    let now = Clock::get()?.unix_timestamp;
+    let mut accounts = remaining_accounts.iter();
+    let mut banks = Vec::new();
+
+    while let Some(bank_info) = accounts.next() {
+        let bank = match loaded_banks.iter().find(|(key, _)| *key == bank_info.key()) {
+            Some((_, bank)) => (*bank).clone(),
+            None => {
+                // Interest is accrued in memory only, so the valuation is current without writing to the bank
+                let mut bank = Account::<Bank>::try_from(bank_info)?.into_inner();
+                accrue_interest(&mut bank, now)?;
+                bank
+            }
+        };
+
+        let price_info = accounts.next().ok_or(ErrorCode::InvalidRemainingAccounts)?;
+        let fallback_price_info = match bank.fallback_oracle {
+            Some(_) => Some(accounts.next().ok_or(ErrorCode::InvalidRemainingAccounts)?),
+            None => None,
+        };
+
+        banks.push((bank_info.key(), bank, price_info, fallback_price_info));
+    }
+
+    Ok(banks)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn health(weighted_collateral: u64, debt: u64, emode: Option<EModeCategory>) -> Health {
+        Health {
+            weighted_collateral: Decimal::from(weighted_collateral),
+            debt: Decimal::from(debt),
+            prices: Vec::new(),
+            emode,
+        }
+    }
+
+    fn bank(min_liquidation_bonus: u64, liquidation_bonus: u64, liquidation_bonus_duration: u64) -> Bank {
+        Bank {
+            min_liquidation_bonus,
+            liquidation_bonus,
+            liquidation_bonus_duration,
+            ..Default::default()
+        }
+    }
+
+    fn category(id: u8, liquidation_bonus: u64) -> EModeCategory {
+        EModeCategory {
+            id,
+            liquidation_bonus,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn liquidation_bonus_is_the_shortfall_clamped_between_min_and_max() {
+        let bank = bank(200, 1_000, 0);
+
+        assert_eq!(health(99, 100, None).liquidation_bonus(&bank, 0).unwrap(), 200);
+        assert_eq!(health(95, 100, None).liquidation_bonus(&bank, 0).unwrap(), 500);
+        assert_eq!(health(50, 100, None).liquidation_bonus(&bank, 0).unwrap(), 1_000);
+        assert_eq!(health(100, 100, None).liquidation_bonus(&bank, 0).unwrap(), 200);
+    }
+
+    #[test]
+    fn liquidation_bonus_uses_the_emode_maximum_for_banks_in_the_category() {
+        let mut bank = bank(200, 1_000, 0);
+        bank.emode_category = 1;
+
+        assert_eq!(health(50, 100, Some(category(1, 300))).liquidation_bonus(&bank, 0).unwrap(), 300);
+        assert_eq!(health(50, 100, Some(category(2, 300))).liquidation_bonus(&bank, 0).unwrap(), 1_000);
+        // The bank's minimum never exceeds the category's maximum
+        assert_eq!(health(99, 100, Some(category(1, 100))).liquidation_bonus(&bank, 0).unwrap(), 100);
+    }
+
+    #[test]
+    fn liquidation_bonus_ramps_from_min_to_max_over_the_duration() {
+        let bank = bank(200, 1_000, 1_000);
+        let health = health(99, 100, None);
+
+        assert_eq!(health.liquidation_bonus(&bank, -5).unwrap(), 200);
+        assert_eq!(health.liquidation_bonus(&bank, 0).unwrap(), 200);
+        assert_eq!(health.liquidation_bonus(&bank, 500).unwrap(), 600);
+        assert_eq!(health.liquidation_bonus(&bank, 1_000).unwrap(), 1_000);
+        assert_eq!(health.liquidation_bonus(&bank, 5_000).unwrap(), 1_000);
+    }
+
+    #[test]
+    fn liquidation_bonus_takes_the_larger_of_the_shortfall_and_time_bonus() {
+        let bank = bank(200, 1_000, 1_000);
+
+        assert_eq!(health(95, 100, None).liquidation_bonus(&bank, 100).unwrap(), 500);
+        assert_eq!(health(95, 100, None).liquidation_bonus(&bank, 750).unwrap(), 800);
+    }
+}
@@ -1,11 +1,22 @@
 use anchor_lang::prelude::*;
+use anchor_spl::token_2022::spl_token_2022::{ self, extension::{ BaseStateWithExtensions, StateWithExtensions } };
 use anchor_spl::token_interface::{ Mint, TokenAccount, TokenInterface };
+use crate::constants::{BPS_SCALE, MAX_LIQUIDATION_BONUS};
+use crate::events::{BankConfigUpdated, BankFallbackOracleUpdated, BankInitialized, BankOracleUpdated, UserInitialized};
+use crate::interest::accrue_bank_interest;
 use crate::state::*;
+use crate::error::ErrorCode;
 
 #[derive(Accounts)]
 pub struct InitBank<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+        constraint = protocol_config.admin == signer.key() @ ErrorCode::Unauthorized,
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
     pub mint: InterfaceAccount<'info, Mint>,
     #[account(
         init, 
@@ -28,6 +39,32 @@ pub struct InitBank<'info> {
     pub system_program: Program <'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct UpdateBankConfig<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+        constraint = protocol_config.admin == authority.key() @ ErrorCode::Unauthorized,
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(mut)]
+    pub bank: Account<'info, Bank>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateBankOracle<'info> {
+    pub authority: Signer<'info>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+        constraint = protocol_config.admin == authority.key() @ ErrorCode::Unauthorized,
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(mut)]
+    pub bank: Account<'info, Bank>,
+}
+
 #[derive(Accounts)]
 pub struct InitUser<'info> {
     #[account(mut)]
@@ -43,22 +80,153 @@ pub struct InitUser<'info> {
     pub system_program: Program <'info, System>,
 }
 
-pub fn process_init_bank(ctx: Context<InitBank>, liquidation_threshold: u64, max_ltv: u64) -> Result<()> {
+pub fn process_init_bank(ctx: Context<InitBank>, config: BankConfig, oracle: OracleConfig) -> Result<()> {
+    validate_bank_config(&config, &ctx.accounts.protocol_config)?;
+    validate_oracle_config(&oracle)?;
+    validate_mint_extensions(&ctx.accounts.mint.to_account_info(), &ctx.accounts.protocol_config.allowed_mint_extensions)?;
+
     let bank = &mut ctx.accounts.bank;
     bank.mint_address = ctx.accounts.mint.key();
+    bank.decimals = ctx.accounts.mint.decimals;
     bank.authority = ctx.accounts.signer.key();
-    bank.liquidation_threshold = liquidation_threshold;
-    bank.max_ltv = max_ltv;
+    bank.set_config(&config);
+    bank.oracle = oracle;
+    bank.last_updated = Clock::get()?.unix_timestamp;
+
+    emit!(BankInitialized {
+        bank: bank.key(),
+        mint: bank.mint_address,
+        authority: bank.authority,
+        config,
+        oracle,
+        timestamp: bank.last_updated,
+    });
+
+    Ok(())
+}
+
+pub fn process_update_bank_config(ctx: Context<UpdateBankConfig>, config: BankConfig) -> Result<()> {
+    validate_bank_config(&config, &ctx.accounts.protocol_config)?;
+
+    let now = Clock::get()?.unix_timestamp;
+    let bank = &mut ctx.accounts.bank;
+
+    // Interest up to now is charged at the old rates
+    accrue_bank_interest(bank, now)?;
+
+    let old_config = bank.config();
+    bank.set_config(&config);
+
+    emit!(BankConfigUpdated {
+        bank: bank.key(),
+        old_config,
+        new_config: config,
+        timestamp: now,
+    });
+
     Ok(())
 }
 
-pub fn process_init_user(ctx: Context<InitUser>, usdc_address: Pubkey) -> Result<()> {
+pub fn process_update_bank_oracle(ctx: Context<UpdateBankOracle>, oracle: OracleConfig) -> Result<()> {
+    validate_oracle_config(&oracle)?;
+
+    let bank = &mut ctx.accounts.bank;
+    let old_oracle = bank.oracle;
+    bank.oracle = oracle;
+
+    emit!(BankOracleUpdated {
+        bank: bank.key(),
+        old_oracle,
+        new_oracle: oracle,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+pub fn process_update_bank_fallback_oracle(ctx: Context<UpdateBankOracle>, fallback_oracle: Option<OracleConfig>, max_oracle_deviation: u64) -> Result<()> {
+    if let Some(fallback_oracle) = &fallback_oracle {
+        validate_oracle_config(fallback_oracle)?;
+        require!(max_oracle_deviation > 0, ErrorCode::InvalidOracleConfig);
+        require!(max_oracle_deviation <= BPS_SCALE, ErrorCode::InvalidOracleConfig);
+    }
+
+    let bank = &mut ctx.accounts.bank;
+    bank.fallback_oracle = fallback_oracle;
+    bank.max_oracle_deviation = max_oracle_deviation;
+
+    emit!(BankFallbackOracleUpdated {
+        bank: bank.key(),
+        fallback_oracle,
+        max_oracle_deviation,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+pub fn process_init_user(ctx: Context<InitUser>) -> Result<()> {
     let user = &mut ctx.accounts.user_account;
     user.owner = ctx.accounts.signer.key();
-    user.usdc_address = usdc_address;
     
     let now = Clock::get()?.unix_timestamp; 
     user.last_updated = now;
 
+    emit!(UserInitialized {
+        user: user.key(),
+        owner: user.owner,
+        timestamp: now,
+    });
+
+    Ok(())
+}
+
+// Extensions such as a permanent delegate, non-transferable tokens or transfer hooks let a third party move
+// or freeze the treasury's tokens, so every extension on the mint has to be explicitly allowed
+fn validate_mint_extensions(mint: &AccountInfo, allowed_extensions: &[u16]) -> Result<()> {
+    if *mint.owner != spl_token_2022::ID {
+        return Ok(());
+    }
+
+    let data = mint.try_borrow_data()?;
+    let mint = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    for extension in mint.get_extension_types()? {
+        require!(allowed_extensions.contains(&u16::from(extension)), ErrorCode::MintExtensionNotAllowed);
+    }
+    Ok(())
+}
+
+pub(crate) fn validate_oracle_config(oracle: &OracleConfig) -> Result<()> {
+    if oracle.oracle_type == OracleType::Fixed {
+        require!(oracle.fixed_price > 0, ErrorCode::InvalidOracleConfig);
+    } else {
+        require!(oracle.max_staleness > 0, ErrorCode::InvalidOracleConfig);
+    }
+    require!(oracle.max_confidence_bps > 0, ErrorCode::InvalidOracleConfig);
+    require!(oracle.max_confidence_bps <= BPS_SCALE, ErrorCode::InvalidOracleConfig);
+    Ok(())
+}
+
+// max_ltv < liquidation_threshold < 100% leaves a buffer between the most a user can borrow and the point
+// they can be liquidated, so a new borrow never starts out liquidatable.
+fn validate_bank_config(config: &BankConfig, protocol_config: &ProtocolConfig) -> Result<()> {
+    require!(config.max_ltv < config.liquidation_threshold, ErrorCode::InvalidBankConfig);
+    require!(config.liquidation_threshold < BPS_SCALE, ErrorCode::InvalidBankConfig);
+    require!(config.liquidation_bonus <= MAX_LIQUIDATION_BONUS, ErrorCode::InvalidBankConfig);
+    require!(config.min_liquidation_bonus <= config.liquidation_bonus, ErrorCode::InvalidBankConfig);
+    require!(config.liquidation_close_factor > 0, ErrorCode::InvalidBankConfig);
+    require!(config.liquidation_close_factor <= BPS_SCALE, ErrorCode::InvalidBankConfig);
+    require!(config.flash_loan_fee <= BPS_SCALE, ErrorCode::InvalidBankConfig);
+    require!(config.origination_fee <= BPS_SCALE, ErrorCode::InvalidBankConfig);
+    require!(config.liquidation_protocol_fee <= BPS_SCALE, ErrorCode::InvalidBankConfig);
+    require!(!config.isolated || config.debt_ceiling > 0, ErrorCode::InvalidBankConfig);
+    require!(config.emode_category == 0 || protocol_config.emode_category(config.emode_category).is_some(), ErrorCode::InvalidEModeCategory);
+    validate_interest_rate_config(&config.interest_rate)
+}
+
+fn validate_interest_rate_config(interest_rate: &InterestRateConfig) -> Result<()> {
+    require!(interest_rate.optimal_utilization > 0, ErrorCode::InvalidInterestRateConfig);
+    require!(interest_rate.optimal_utilization < BPS_SCALE, ErrorCode::InvalidInterestRateConfig);
+    require!(interest_rate.reserve_factor <= BPS_SCALE, ErrorCode::InvalidInterestRateConfig);
     Ok(())
 }
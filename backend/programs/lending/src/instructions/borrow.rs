@@ -1,17 +1,25 @@
-use std::f32::consts::E;
-
 use anchor_lang::prelude::*;
 use anchor_spl::associated_token::AssociatedToken;
 use anchor_spl::token_interface::{ self, Mint, TokenAccount, TokenInterface, TransferChecked };
-use pyth_solana_receiver_sdk::price_update::{get_feed_id_from_hex, PriceUpdateV2};
-use crate::constants::{MAXIMUM_AGE, SOL_USD_FEED_ID, USDC_USD_FEED_ID};
+use crate::health::{compute_health, HealthType};
+use crate::interest::accrue_bank_interest;
+use crate::isolation::{isolated_debt_value, update_isolated_bank};
+use crate::math::{Decimal, Rounding};
+use crate::events::BorrowEvent;
 use crate::state::*;
 use crate::error::ErrorCode;
 
+// Remaining accounts: a [bank, price update] pair for every open position of the user, see `compute_health`.
+// The user's isolated collateral bank, if any, must be writable.
 #[derive(Accounts)]
 pub struct Borrow<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
     pub mint: InterfaceAccount<'info, Mint>,
     #[account(
         mut, 
@@ -39,47 +47,87 @@ pub struct Borrow<'info> {
         associated_token::token_program = token_program,
     )]
     pub user_token_account: InterfaceAccount<'info, TokenAccount>, 
-    pub price_update: Account<'info, PriceUpdateV2>,
     pub token_program: Interface<'info, TokenInterface>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
-// 1. Check if user has enough collateral to borrow
-// 2. Warn if borrowing beyond the safe amount but still allow if within the max borrowable amount
-// 3. Make a CPI transfer from the bank's token account to the user's token account
-// 4. Update the user's borrowed amount and total borrowed value
-// 5. Update the bank's total borrows and total borrow shares
+// 1. Add the origination fee to the amount owed, check the asset can be borrowed in isolation mode
+// 2. Update the user's borrow shares
+// 3. Update the bank's total borrows, total borrow shares and protocol fees
+// 4. Check the user's health against max LTV with the new debt included
+// 5. Count the debt's USD value against the isolated bank's debt ceiling
+// 6. Make a CPI transfer from the bank's token account to the user's token account
+
+pub fn process_borrow<'info>(ctx: Context<'_, '_, 'info, 'info, Borrow<'info>>, amount: u64) -> Result<()> {
+    ctx.accounts.protocol_config.require_not_paused(&[&ctx.accounts.bank], Operation::Borrow)?;
+    require!(ctx.accounts.bank.flash_loan_amount == 0, ErrorCode::FlashLoanInProgress);
+
+    let now = Clock::get()?.unix_timestamp;
+    accrue_bank_interest(&mut ctx.accounts.bank, now)?;
 
-pub fn process_borrow(ctx: Context<Borrow>, amount: u64) -> Result<()> {
-    // Check if user has enough collateral to borrow
     let bank = &mut ctx.accounts.bank;
     let user = &mut ctx.accounts.user_account;
 
-    let price_update = &mut ctx.accounts.price_update;
-
-    let total_collateral: u64;
+    // The user receives `amount` but owes it plus the fee. Like the interest reserve, the fee is booked to the
+    // protocol right away and can be collected before the debt is repaid, out of the treasury's idle liquidity.
+    let fee = Decimal::from(amount)
+        .try_mul(Decimal::from_bps(bank.origination_fee), Rounding::Up)?
+        .to_u64(Rounding::Up)?;
+    let debt = amount.checked_add(fee).ok_or(ErrorCode::MathOverflow)?;
 
-    match ctx.accounts.mint.to_account_info().key() {
-        key if key == user.usdc_address => {
-            let sol_feed_id = get_feed_id_from_hex(SOL_USD_FEED_ID)?; 
-            let sol_price = price_update.get_price_no_older_than(&Clock::get()?, MAXIMUM_AGE, &sol_feed_id)?;
-            let accrued_interest = calculate_accrued_interest(user.deposited_sol, bank.interest_rate, user.last_updated)?;
-            total_collateral = sol_price.price as u64 * (user.deposited_sol + accrued_interest);
-        },
-        _ => {
-            let usdc_feed_id = get_feed_id_from_hex(USDC_USD_FEED_ID)?;
-            let usdc_price = price_update.get_price_no_older_than(&Clock::get()?, MAXIMUM_AGE, &usdc_feed_id)?;
-            total_collateral = usdc_price.price as u64 * user.deposited_usdc;
+    if bank.borrow_cap > 0 {
+        let total_borrowed = bank.total_borrowed.checked_add(debt).ok_or(ErrorCode::MathOverflow)?;
+        require!(total_borrowed <= bank.borrow_cap, ErrorCode::BorrowCapExceeded);
+    }
 
-        }
+    // Debt backed by isolated collateral is limited to approved assets
+    let isolated_bank = user.isolated_bank();
+    if isolated_bank.is_some() {
+        require!(bank.borrowable_in_isolation, ErrorCode::NotBorrowableInIsolation);
     }
 
-    let borrowable_amount = total_collateral as u64 *  bank.liquidation_threshold;
+    // Debt shares minted for the user always round up
+    let users_shares = bank.borrow_shares(debt, Rounding::Up)?;
+
+    bank.total_borrowed = bank.total_borrowed.checked_add(debt).ok_or(ErrorCode::MathOverflow)?;
+    bank.total_borrowed_shares = bank.total_borrowed_shares.checked_add(users_shares).ok_or(ErrorCode::MathOverflow)?;
+    bank.protocol_fees = bank.protocol_fees.checked_add(fee).ok_or(ErrorCode::MathOverflow)?;
 
-    if borrowable_amount < amount {
-        return Err(ErrorCode::OverBorrowableAmount.into());
-    }       
+    let position = user.position_mut_or_open(&bank.key(), &ctx.accounts.mint.key())?;
+
+    position.borrowed_shares = position.borrowed_shares.checked_add(users_shares).ok_or(ErrorCode::MathOverflow)?;
+
+    // Check if user has enough collateral to borrow
+    let health = compute_health(user, &ctx.accounts.protocol_config, &[(bank.key(), bank)], ctx.remaining_accounts, HealthType::Initial)?;
+    if !health.is_healthy() {
+        return Err(ErrorCode::OverLTV.into());
+    }
+    user.health_factor = health.factor()?.to_scaled_val()?;
+    user.liquidatable_since = 0;
+
+    // Isolated debt is counted against the ceiling at its USD value, priced by the health check above
+    if let Some(isolated_bank) = isolated_bank {
+        let isolated_debt = isolated_debt_value(debt, health.price(&bank.key())?)?;
+        update_isolated_bank(isolated_bank, bank, ctx.remaining_accounts, |isolated_bank| {
+            isolated_bank.add_isolated_debt(isolated_debt)
+        })?;
+        let position = user.position_mut(&bank.key())?;
+        position.isolated_debt = position.isolated_debt.checked_add(isolated_debt).ok_or(ErrorCode::MathOverflow)?;
+    }
+
+    emit!(BorrowEvent {
+        user: user.owner,
+        bank: bank.key(),
+        amount,
+        shares: users_shares,
+        total_borrowed: bank.total_borrowed,
+        total_borrowed_shares: bank.total_borrowed_shares,
+        fee,
+        price: health.price(&bank.key())?.to_scaled_val()?,
+        health_factor: user.health_factor,
+        timestamp: now,
+    });
 
     let transfer_cpi_accounts = TransferChecked {
         from: ctx.accounts.bank_token_account.to_account_info(),
@@ -102,34 +150,5 @@ pub fn process_borrow(ctx: Context<Borrow>, amount: u64) -> Result<()> {
 
     token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
 
-    if bank.total_borrowed == 0 {
-        bank.total_borrowed = amount;
-        bank.total_borrowed_shares = amount;
-    } 
-
-    let borrow_ratio = amount.checked_div(bank.total_borrowed).unwrap();
-    let users_shares = bank.total_borrowed_shares.checked_mul(borrow_ratio).unwrap();
-
-    bank.total_borrowed += amount;
-    bank.total_borrowed_shares += users_shares; 
-
-    match ctx.accounts.mint.to_account_info().key() {
-        key if key == user.usdc_address => {
-            user.borrowed_usdc += amount;
-            user.deposited_usdc_shares += users_shares;
-        },
-        _ => {
-            user.borrowed_sol += amount;
-            user.deposited_sol_shares += users_shares;
-        }
-    }
-
     Ok(())
 }
-
-fn calculate_accrued_interest(deposited: u64, interest_rate: u64, last_update: i64) -> Result<u64> {
-    let current_time = Clock::get()?.unix_timestamp;
-    let time_elapsed = current_time - last_update;
-    let new_value = (deposited as f64 * E.powf(interest_rate as f32 * time_elapsed as f32) as f64) as u64;
-    Ok(new_value)
-}
\ No newline at end of file
@@ -0,0 +1,92 @@
+use anchor_lang::prelude::*;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token_interface::{ self, Mint, TokenAccount, TokenInterface, TransferChecked };
+use crate::interest::accrue_bank_interest;
+use crate::events::FeesCollected;
+use crate::state::*;
+
+// Anyone can sweep fees, they can only ever go to the fee recipient set on the protocol config
+#[derive(Accounts)]
+pub struct CollectFees<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+    #[account(
+        mut,
+        seeds = [b"treasury", mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: Only used to derive the fee recipient's token account
+    #[account(address = protocol_config.fee_recipient)]
+    pub fee_recipient: UncheckedAccount<'info>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = fee_recipient,
+        associated_token::token_program = token_program,
+    )]
+    pub fee_recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+// 1. Accrue interest so the fees include everything earned up to now
+// 2. Collect as much of the fees as the treasury's idle liquidity allows
+// 3. Make a CPI transfer from the bank's token account to the fee recipient's token account
+//
+// Depositor balances are untouched: fees were never part of `total_deposits`. Fees are booked when charged,
+// origination fees included, so part of what is collected may still be owed by borrowers.
+
+pub fn process_collect_fees(ctx: Context<CollectFees>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    accrue_bank_interest(&mut ctx.accounts.bank, now)?;
+
+    let bank = &mut ctx.accounts.bank;
+    let amount = bank.protocol_fees.min(ctx.accounts.bank_token_account.amount);
+    if amount == 0 {
+        return Ok(());
+    }
+    bank.protocol_fees -= amount;
+
+    emit!(FeesCollected {
+        bank: bank.key(),
+        fee_recipient: ctx.accounts.fee_recipient.key(),
+        amount,
+        remaining_fees: bank.protocol_fees,
+        timestamp: now,
+    });
+
+    let transfer_cpi_accounts = TransferChecked {
+        from: ctx.accounts.bank_token_account.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+        to: ctx.accounts.fee_recipient_token_account.to_account_info(),
+        authority: ctx.accounts.bank_token_account.to_account_info(),
+    };
+
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let mint_key = ctx.accounts.mint.key();
+    let signer_seeds: &[&[&[u8]]] = &[
+        &[
+            b"treasury",
+            mint_key.as_ref(),
+            &[ctx.bumps.bank_token_account],
+        ],
+    ];
+    let cpi_ctx = CpiContext::new(cpi_program, transfer_cpi_accounts).with_signer(signer_seeds);
+    let decimals = ctx.accounts.mint.decimals;
+
+    token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
+
+    Ok(())
+}
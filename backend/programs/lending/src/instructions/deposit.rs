@@ -1,12 +1,22 @@
 use anchor_lang::prelude::*;
 use anchor_spl::associated_token::AssociatedToken;
-use anchor_spl::token_interface::{ self, Mint, TokenAccount, TokenInterface, TransferChecked };
+use anchor_spl::token_interface::{ Mint, TokenAccount, TokenInterface };
+use crate::interest::accrue_bank_interest;
+use crate::transfer::transfer_in;
+use crate::math::Rounding;
+use crate::events::DepositEvent;
 use crate::state::*;
+use crate::error::ErrorCode;
 
 #[derive(Accounts)]
 pub struct Deposit<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
     pub mint: InterfaceAccount<'info, Mint>,
     #[account(
         mut, 
@@ -39,59 +49,66 @@ pub struct Deposit<'info> {
 }
 
 // 1. CPI transfer from user's token account to bank's token account
-// 2. Calculate new shares to be added to the bank
-// 3. Update user's deposited amount and total collateral value
+// 2. Calculate new shares to be minted for the amount received at the bank's current exchange rate
+// 3. Update user's deposit shares, opening the position as collateral unless isolation mode forbids it
 // 4. Update bank's total deposits and total deposit shares
 // 5. Update users health factor ?? 
 
 pub fn process_deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
-    let transfer_cpi_accounts = TransferChecked {
-        from: ctx.accounts.user_token_account.to_account_info(),
-        mint: ctx.accounts.mint.to_account_info(),
-        to: ctx.accounts.bank_token_account.to_account_info(),
-        authority: ctx.accounts.signer.to_account_info(),
-    };
+    ctx.accounts.protocol_config.require_not_paused(&[&ctx.accounts.bank], Operation::Deposit)?;
 
-    let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_ctx = CpiContext::new(cpi_program, transfer_cpi_accounts);
-    let decimals = ctx.accounts.mint.decimals;
+    let now = Clock::get()?.unix_timestamp;
+    accrue_bank_interest(&mut ctx.accounts.bank, now)?;
 
-    token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
+    let bank = &ctx.accounts.bank;
+    if bank.deposit_cap > 0 {
+        let total_deposits = bank.total_deposits.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        require!(total_deposits <= bank.deposit_cap, ErrorCode::DepositCapExceeded);
+    }
 
-    // calculate new shares to be added to the bank
-    let bank = &mut ctx.accounts.bank;
+    let received = transfer_in(
+        &ctx.accounts.token_program,
+        &ctx.accounts.mint,
+        &ctx.accounts.user_token_account,
+        &mut ctx.accounts.bank_token_account,
+        &ctx.accounts.signer,
+        amount,
+    )?;
 
-    // Note: The checked_ prefix in Rust is used to perform operations safely by checking for potential 
-    // arithmetic overflow or other errors that could occur during the computation. If such an error occurs, 
-    // these methods return None instead of causing a panic.
+    // Note: Share math goes through `Decimal`, which returns `MathOverflow` instead of panicking and lets
+    // each step choose its rounding direction. Shares credited to the user always round down.
+    let users_shares = ctx.accounts.bank.deposit_shares(received, Rounding::Down)?;
+    require!(users_shares > 0, ErrorCode::ZeroShares);
 
-    if bank.total_deposits == 0 {
-        bank.total_deposits = amount;
-        bank.total_deposit_shares = amount;
-    }
-    
-    let deposit_ratio = amount.checked_div(bank.total_deposits).unwrap();
-    let users_shares = bank.total_deposit_shares.checked_mul(deposit_ratio).unwrap();
-    
+    let bank = &mut ctx.accounts.bank;
     let user = &mut ctx.accounts.user_account;
-    
-    match ctx.accounts.mint.to_account_info().key() {
-        key if key == user.usdc_address => {
-            user.deposited_usdc += amount;
-            user.deposited_usdc_shares += users_shares;
-        },
-        _ => {
-            user.deposited_sol += amount;
-            user.deposited_sol_shares += users_shares; 
-        }
+    let can_use_as_collateral = user.can_use_as_collateral(&bank.key(), bank.isolated);
+    let position = user.position_mut_or_open(&bank.key(), &ctx.accounts.mint.key())?;
+
+    // Under isolation mode a new deposit that cannot be combined with the user's collateral only earns yield
+    if position.deposited_shares == 0 && !can_use_as_collateral {
+        position.is_collateral = false;
+    }
+    position.deposited_shares = position.deposited_shares.checked_add(users_shares).ok_or(ErrorCode::MathOverflow)?;
+
+    if bank.isolated && position.is_collateral {
+        user.isolated_bank = bank.key();
     }
 
-    // The above match statement can easily have new branches added when additional assets are added to the protocol
+    bank.total_deposits = bank.total_deposits.checked_add(received).ok_or(ErrorCode::MathOverflow)?;
+    bank.total_deposit_shares = bank.total_deposit_shares.checked_add(users_shares).ok_or(ErrorCode::MathOverflow)?;
 
-    bank.total_deposits += amount;
-    bank.total_deposit_shares += users_shares;
+    user.last_updated = now;
 
-    user.last_updated = Clock::get()?.unix_timestamp;
+    emit!(DepositEvent {
+        user: user.owner,
+        bank: bank.key(),
+        amount: received,
+        shares: users_shares,
+        total_deposits: bank.total_deposits,
+        total_deposit_shares: bank.total_deposit_shares,
+        timestamp: now,
+    });
 
     Ok(())
 }
\ No newline at end of file
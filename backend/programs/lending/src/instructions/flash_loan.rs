@@ -0,0 +1,188 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar::instructions::{ self as instructions_sysvar, load_current_index_checked, load_instruction_at_checked };
+use anchor_lang::Discriminator;
+use anchor_spl::associated_token::AssociatedToken;
+use anchor_spl::token_interface::{ self, Mint, TokenAccount, TokenInterface, TransferChecked };
+use crate::math::{Decimal, Rounding};
+use crate::transfer::transfer_in;
+use crate::events::{FlashBorrowEvent, FlashRepayEvent};
+use crate::state::*;
+use crate::error::ErrorCode;
+
+// Position of `bank` in the `FlashRepay` accounts, used to match a repayment to its loan
+const FLASH_REPAY_BANK_INDEX: usize = 2;
+
+#[derive(Accounts)]
+pub struct FlashBorrow<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+    #[account(
+        mut,
+        seeds = [b"treasury", mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init_if_needed,
+        payer = signer,
+        associated_token::mint = mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program,
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    /// CHECK: Instructions sysvar, used to find the matching `flash_repay`
+    #[account(address = instructions_sysvar::ID)]
+    pub instructions: UncheckedAccount<'info>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FlashRepay<'info> {
+    pub signer: Signer<'info>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+    #[account(
+        mut,
+        seeds = [b"treasury", mint.key().as_ref()],
+        bump,
+    )]
+    pub bank_token_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = signer,
+        associated_token::token_program = token_program,
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+// 1. Check the loan is taken directly by the transaction and not through a CPI
+// 2. Find a `flash_repay` for the same bank later in the transaction
+// 3. Record the outstanding amount, blocking borrows and withdrawals from the bank until it is repaid
+// 4. Make a CPI transfer from the bank's token account to the user's token account
+
+pub fn process_flash_borrow(ctx: Context<FlashBorrow>, amount: u64) -> Result<()> {
+    ctx.accounts.protocol_config.require_not_paused(&[&ctx.accounts.bank], Operation::FlashLoan)?;
+    require!(ctx.accounts.bank.flash_loan_amount == 0, ErrorCode::FlashLoanInProgress);
+
+    let instructions = ctx.accounts.instructions.to_account_info();
+    let current_index = load_current_index_checked(&instructions)? as usize;
+
+    // The sysvar only lists top-level instructions, so a CPI caller would be checked against its own instruction
+    let current = load_instruction_at_checked(current_index, &instructions)?;
+    require_keys_eq!(current.program_id, crate::ID, ErrorCode::FlashLoanCpi);
+
+    let bank_key = ctx.accounts.bank.key();
+    let mut index = current_index + 1;
+    loop {
+        let Ok(instruction) = load_instruction_at_checked(index, &instructions) else {
+            return Err(ErrorCode::MissingFlashRepay.into());
+        };
+
+        let is_flash_repay = instruction.program_id == crate::ID
+            && instruction.data.get(..8) == Some(&crate::instruction::FlashRepay::DISCRIMINATOR[..]);
+        let same_bank = instruction.accounts.get(FLASH_REPAY_BANK_INDEX).map(|account| account.pubkey) == Some(bank_key);
+
+        if is_flash_repay && same_bank {
+            break;
+        }
+        index += 1;
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+    let bank = &mut ctx.accounts.bank;
+    bank.flash_loan_amount = amount;
+
+    emit!(FlashBorrowEvent {
+        user: ctx.accounts.signer.key(),
+        bank: bank_key,
+        amount,
+        timestamp: now,
+    });
+
+    let transfer_cpi_accounts = TransferChecked {
+        from: ctx.accounts.bank_token_account.to_account_info(),
+        mint: ctx.accounts.mint.to_account_info(),
+        to: ctx.accounts.user_token_account.to_account_info(),
+        authority: ctx.accounts.bank_token_account.to_account_info(),
+    };
+
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let mint_key = ctx.accounts.mint.key();
+    let signer_seeds: &[&[&[u8]]] = &[
+        &[
+            b"treasury",
+            mint_key.as_ref(),
+            &[ctx.bumps.bank_token_account],
+        ],
+    ];
+    let cpi_ctx = CpiContext::new(cpi_program, transfer_cpi_accounts).with_signer(signer_seeds);
+    let decimals = ctx.accounts.mint.decimals;
+
+    token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
+
+    Ok(())
+}
+
+// 1. Calculate the flash loan fee on the outstanding amount
+// 2. Make a CPI transfer of the amount plus fee from the user's token account to the bank's token account
+// 3. Check the amount received covers the loan
+// 4. Clear the outstanding amount and add the rest of what was received to the bank's protocol fees
+
+pub fn process_flash_repay(ctx: Context<FlashRepay>) -> Result<()> {
+    let amount = ctx.accounts.bank.flash_loan_amount;
+    require!(amount > 0, ErrorCode::NoFlashLoan);
+
+    // Fees charged to the user always round up
+    let fee = Decimal::from(amount)
+        .try_mul(Decimal::from_bps(ctx.accounts.bank.flash_loan_fee), Rounding::Up)?
+        .to_u64(Rounding::Up)?;
+    let repay_amount = amount.checked_add(fee).ok_or(ErrorCode::MathOverflow)?;
+
+    let received = transfer_in(
+        &ctx.accounts.token_program,
+        &ctx.accounts.mint,
+        &ctx.accounts.user_token_account,
+        &mut ctx.accounts.bank_token_account,
+        &ctx.accounts.signer,
+        repay_amount,
+    )?;
+
+    // The loan must come back in full, only what is left over is booked as the fee
+    require!(received >= amount, ErrorCode::FlashRepayShortfall);
+    let fee = received - amount;
+
+    let bank = &mut ctx.accounts.bank;
+    bank.flash_loan_amount = 0;
+    bank.protocol_fees = bank.protocol_fees.checked_add(fee).ok_or(ErrorCode::MathOverflow)?;
+
+    emit!(FlashRepayEvent {
+        user: ctx.accounts.signer.key(),
+        bank: bank.key(),
+        amount,
+        fee,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+use crate::interest::accrue_bank_interest;
+use crate::isolation::update_isolated_bank;
+use crate::math::Rounding;
+use crate::events::BadDebtWrittenOff;
+use crate::state::*;
+use crate::error::ErrorCode;
+
+// Anyone can write off bad debt, it is only allowed once the user has no deposits left at all
+// Remaining accounts: the user's isolated bank, writable, while the position has debt counted against its ceiling
+#[derive(Accounts)]
+pub struct HandleBadDebt<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        seeds = [mint.key().as_ref()],
+        bump,
+    )]
+    pub bank: Account<'info, Bank>,
+    /// User account of the borrower whose debt is written off
+    #[account(mut)]
+    pub user_account: Account<'info, User>,
+}
+
+// 1. Check the user has no deposits left and still owes the bank
+// 2. Burn the user's debt shares, remove the debt from the bank's total borrows and the isolated debt ceiling
+// 3. Cover the debt from the bank's protocol fees first
+// 4. Socialize the rest by reducing total deposits, which lowers the value of every deposit share
+
+pub fn process_handle_bad_debt<'info>(ctx: Context<'_, '_, 'info, 'info, HandleBadDebt<'info>>) -> Result<()> {
+    ctx.accounts.protocol_config.require_not_paused(&[&ctx.accounts.bank], Operation::Liquidate)?;
+
+    let now = Clock::get()?.unix_timestamp;
+    accrue_bank_interest(&mut ctx.accounts.bank, now)?;
+
+    let bank = &mut ctx.accounts.bank;
+    let user = &mut ctx.accounts.user_account;
+
+    // Deposits not enabled as collateral cannot be seized, but the user must not keep them while their debt is
+    // socialized, so they have to be used to repay first
+    let has_deposits = user.positions.iter().any(|position| position.deposited_shares > 0);
+    require!(!has_deposits, ErrorCode::UserHasDeposits);
+
+    let shares = user.position(&bank.key()).map_or(0, |position| position.borrowed_shares);
+    require!(shares > 0, ErrorCode::NoBadDebt);
+    let amount = bank.borrow_amount(shares, Rounding::Up)?;
+
+    let position = user.position_mut(&bank.key())?;
+    let isolated_debt = position.release_isolated_debt(shares)?;
+    position.borrowed_shares = 0;
+
+    // Individual debts round up, so their sum can exceed the bank's total by a few units
+    bank.total_borrowed = bank.total_borrowed.saturating_sub(amount);
+    bank.total_borrowed_shares = bank.total_borrowed_shares.checked_sub(shares).ok_or(ErrorCode::MathOverflow)?;
+
+    if isolated_debt > 0 {
+        update_isolated_bank(user.isolated_bank, bank, ctx.remaining_accounts, |isolated_bank| {
+            isolated_bank.remove_isolated_debt(isolated_debt);
+            Ok(())
+        })?;
+    }
+
+    // Fees are the protocol's claim on the treasury, giving them up leaves those tokens to depositors
+    let covered_by_reserves = amount.min(bank.protocol_fees);
+    bank.protocol_fees -= covered_by_reserves;
+
+    let socialized = amount - covered_by_reserves;
+    bank.total_deposits = bank.total_deposits.saturating_sub(socialized);
+
+    if user.positions.iter().all(|position| position.borrowed_shares == 0) {
+        user.health_factor = u128::MAX;
+        user.liquidatable_since = 0;
+    }
+    user.last_updated = now;
+
+    emit!(BadDebtWrittenOff {
+        user: user.owner,
+        bank: bank.key(),
+        amount,
+        shares,
+        covered_by_reserves,
+        socialized,
+        total_deposits: bank.total_deposits,
+        total_borrowed: bank.total_borrowed,
+        timestamp: now,
+    });
+
+    Ok(())
+}
@@ -1,16 +1,26 @@
 use anchor_lang::prelude::*;
 use anchor_spl::associated_token::AssociatedToken;
 use anchor_spl::token_interface::{ self, Mint, TokenAccount, TokenInterface, TransferChecked };
-use pyth_solana_receiver_sdk::price_update::{get_feed_id_from_hex, PriceUpdateV2};
-use crate::constants::{MAXIMUM_AGE, SOL_USD_FEED_ID, USDC_USD_FEED_ID};
+use crate::health::{compute_health, HealthType};
+use crate::interest::accrue_bank_interest;
+use crate::isolation::update_isolated_bank;
+use crate::transfer::transfer_in;
+use crate::math::{Decimal, Rounding};
+use crate::events::LiquidationEvent;
 use crate::state::*;
 use crate::error::ErrorCode;
 
+// Remaining accounts: a [bank, price update] pair for every open position of the borrower, see `compute_health`.
+// The borrower's isolated bank must be writable if the repaid position has debt counted against its ceiling.
 #[derive(Accounts)]
 pub struct Liquidate<'info> {
     #[account(mut)]
     pub liquidator: Signer<'info>,
-    pub price_update: Account<'info, PriceUpdateV2>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
     pub collateral_mint: InterfaceAccount<'info, Mint>,
     pub borrowed_mint: InterfaceAccount<'info, Mint>,
     #[account(
@@ -37,12 +47,9 @@ pub struct Liquidate<'info> {
         bump, 
     )]  
     pub borrowed_bank_token_account: InterfaceAccount<'info, TokenAccount>,
-    #[account(
-        mut, 
-        seeds = [liquidator.key().as_ref()],
-        bump,
-    )]  
-    pub user_account: Account<'info, User>,
+    /// User account of the borrower being liquidated
+    #[account(mut)]
+    pub borrower_account: Account<'info, User>,
     #[account( 
         init_if_needed, 
         payer = liquidator,
@@ -64,55 +71,176 @@ pub struct Liquidate<'info> {
     pub system_program: Program<'info, System>,
 }
 
-// 1. Check if user is undercollateralized
-// 2. Calculate liquidation amount
-// 3. Make a CPI transfer from the user's token account to the bank's token account
-// 4. Update the user and bank states
-// 5. Handle fees and rewards 
+// 1. Check the banks differ and the borrower is undercollateralized
+// 2. Check the repay amount against the close factor, cap it to what the remaining collateral can pay for
+// 3. Make a CPI transfer from the liquidator's token account to the borrowed bank
+// 4. Convert the amount received into collateral plus a bonus scaled by the borrower's shortfall and how
+//    long they have been liquidatable, or seize all of the collateral if the repayment was capped
+// 5. Burn the borrower's repaid debt shares and seized deposit shares, update both banks and the protocol fees
+// 6. Make a CPI transfer of the seized collateral to the liquidator
 
-pub fn process_liquidate(ctx: Context<Liquidate>) -> Result<()> { 
-    let collateral_bank = &mut ctx.accounts.collateral_bank;
-    let user = &mut ctx.accounts.user_account;
+pub fn process_liquidate<'info>(ctx: Context<'_, '_, 'info, 'info, Liquidate<'info>>, repay_amount: u64) -> Result<()> { 
+    ctx.accounts.protocol_config.require_not_paused(&[&ctx.accounts.collateral_bank, &ctx.accounts.borrowed_bank], Operation::Liquidate)?;
+    require!(repay_amount > 0, ErrorCode::ZeroLiquidationAmount);
+    // Both banks are separate mutable copies, with the same mint the collateral side updates would be lost on exit
+    require_keys_neq!(ctx.accounts.collateral_bank.key(), ctx.accounts.borrowed_bank.key(), ErrorCode::SameLiquidationBank);
 
-    let price_update = &mut ctx.accounts.price_update;
+    let now = Clock::get()?.unix_timestamp;
+    accrue_bank_interest(&mut ctx.accounts.collateral_bank, now)?;
+    accrue_bank_interest(&mut ctx.accounts.borrowed_bank, now)?;
 
-    let sol_feed_id = get_feed_id_from_hex(SOL_USD_FEED_ID)?; 
-    let usdc_feed_id = get_feed_id_from_hex(USDC_USD_FEED_ID)?;
+    let collateral_bank = &mut ctx.accounts.collateral_bank;
+    let borrowed_bank = &mut ctx.accounts.borrowed_bank;
+    let borrower = &mut ctx.accounts.borrower_account;
+
+    let loaded_banks = [
+        (collateral_bank.key(), &**collateral_bank),
+        (borrowed_bank.key(), &**borrowed_bank),
+    ];
+    let health = compute_health(borrower, &ctx.accounts.protocol_config, &loaded_banks, ctx.remaining_accounts, HealthType::Maintenance)?;
 
-    let sol_price = price_update.get_price_no_older_than(&Clock::get()?, MAXIMUM_AGE, &sol_feed_id)?;
-    let usdc_price = price_update.get_price_no_older_than(&Clock::get()?, MAXIMUM_AGE, &usdc_feed_id)?;
+    if health.is_healthy() {
+        return Err(ErrorCode::NotUndercollateralized.into());
+    }
+    let health_factor_before = health.factor()?.to_scaled_val()?;
 
-    // Note: For simplicity, interest is not being included in these calculations. 
+    // The time based bonus starts from 0 on the first liquidation unless a keeper started the clock earlier
+    // through `update_user_health`
+    if borrower.liquidatable_since == 0 {
+        borrower.liquidatable_since = now;
+    }
 
-    let total_collateral = (sol_price.price as u64 * user.deposited_sol) + (usdc_price.price as u64 * user.deposited_usdc);
-    let total_borrowed = (sol_price.price as u64 * user.borrowed_sol) + (usdc_price.price as u64 * user.borrowed_usdc);    
+    // Balances are derived from shares, so they include the interest accrued above
+    let borrowed_shares = borrower.position(&borrowed_bank.key()).map_or(0, |position| position.borrowed_shares);
+    let borrowed_amount = borrowed_bank.borrow_amount(borrowed_shares, Rounding::Up)?;
 
-    let health_factor = (total_collateral * collateral_bank.liquidation_threshold)/total_borrowed;
+    let max_repay = Decimal::from(borrowed_amount)
+        .try_mul(Decimal::from_bps(borrowed_bank.liquidation_close_factor), Rounding::Down)?
+        .to_u64(Rounding::Down)?;
 
-    if health_factor >= 1 {
-        return Err(ErrorCode::NotUndercollateralized.into());
+    if repay_amount > max_repay {
+        return Err(ErrorCode::CloseFactorExceeded.into());
     }
 
-    let liquidation_amount = total_borrowed * collateral_bank.liquidation_close_factor;
+    // Collateral seized is worth the repaid debt plus the liquidation bonus, converted through both prices.
+    // The protocol keeps its cut of the bonus in the collateral bank's treasury.
+    let borrowed_price = health.price(&borrowed_bank.key())?;
+    let collateral_price = health.price(&collateral_bank.key())?;
+    let liquidation_bonus = health.liquidation_bonus(collateral_bank, now - borrower.liquidatable_since)?;
+    let bonus_factor = Decimal::one().try_add(Decimal::from_bps(liquidation_bonus))?;
+
+    // Deposits the borrower has not enabled as collateral are not backing the debt and cannot be seized
+    let collateral_shares = borrower
+        .position(&collateral_bank.key())
+        .filter(|position| position.is_collateral)
+        .map_or(0, |position| position.deposited_shares);
+    require!(collateral_shares > 0, ErrorCode::InsufficientCollateral);
+    let collateral_amount = collateral_bank.deposit_amount(collateral_shares, Rounding::Down)?;
+
+    // A repayment whose collateral plus bonus would exceed what is left is capped and seizes all of it, so no
+    // collateral too small to liquidate is left behind and `handle_bad_debt` can write off the rest of the debt.
+    // At least one base unit is repaid, even when the remaining collateral is worth less.
+    let max_seizable_repay = Decimal::from(collateral_amount)
+        .try_mul(collateral_price, Rounding::Down)?
+        .try_div(borrowed_price, Rounding::Down)?
+        .try_div(bonus_factor, Rounding::Down)?
+        .to_u64(Rounding::Down)?
+        .max(1);
+    let seizes_all = repay_amount >= max_seizable_repay;
+    let repay_amount = repay_amount.min(max_seizable_repay);
 
     // liquidator pays back the borrowed amount back to the bank 
+    let repaid_amount = transfer_in(
+        &ctx.accounts.token_program,
+        &ctx.accounts.borrowed_mint,
+        &ctx.accounts.liquidator_borrowed_token_account,
+        &mut ctx.accounts.borrowed_bank_token_account,
+        &ctx.accounts.liquidator,
+        repay_amount,
+    )?;
 
-    let transfer_to_bank = TransferChecked {
-        from: ctx.accounts.liquidator_borrowed_token_account.to_account_info(),
-        mint: ctx.accounts.borrowed_mint.to_account_info(),
-        to: ctx.accounts.borrowed_bank_token_account.to_account_info(),
-        authority: ctx.accounts.liquidator.to_account_info(),
+    let repaid_collateral = Decimal::from(repaid_amount)
+        .try_mul(borrowed_price, Rounding::Down)?
+        .try_div(collateral_price, Rounding::Down)?;
+    let (seized_amount, seized_shares) = if seizes_all {
+        (collateral_amount, collateral_shares)
+    } else {
+        let seized_amount = repaid_collateral.try_mul(bonus_factor, Rounding::Down)?.to_u64(Rounding::Down)?;
+        // Rounding the burned shares up can overshoot the position by a share
+        (seized_amount, collateral_bank.deposit_shares(seized_amount, Rounding::Up)?.min(collateral_shares))
     };
 
-    let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_ctx_to_bank = CpiContext::new(cpi_program.clone(), transfer_to_bank);
-    let decimals = ctx.accounts.borrowed_mint.decimals;
+    let bonus = Decimal::from(seized_amount).try_sub(repaid_collateral.min(Decimal::from(seized_amount)))?;
+    let protocol_fee = bonus
+        .try_mul(Decimal::from_bps(collateral_bank.liquidation_protocol_fee), Rounding::Down)?
+        .to_u64(Rounding::Down)?;
+    let liquidator_amount = seized_amount.checked_sub(protocol_fee).ok_or(ErrorCode::MathOverflow)?;
+
+    let repaid_shares = if repaid_amount == borrowed_amount {
+        borrowed_shares
+    } else {
+        borrowed_bank.borrow_shares(repaid_amount, Rounding::Down)?
+    };
 
-    token_interface::transfer_checked(cpi_ctx_to_bank, liquidation_amount, decimals)?;
+    let position = borrower.position_mut(&borrowed_bank.key())?;
+    let isolated_debt = position.release_isolated_debt(repaid_shares)?;
+    position.borrowed_shares = position.borrowed_shares.checked_sub(repaid_shares).ok_or(ErrorCode::MathOverflow)?;
 
-    // Transfer liquidation value and bonus to liquidator
-    let liquidation_bonus = (liquidation_amount * collateral_bank.liquidation_bonus) + liquidation_amount;
-    
+    // Individual debts round up, so their sum can exceed the bank's total by a few units
+    borrowed_bank.total_borrowed = borrowed_bank.total_borrowed.saturating_sub(repaid_amount);
+    borrowed_bank.total_borrowed_shares = borrowed_bank.total_borrowed_shares.checked_sub(repaid_shares).ok_or(ErrorCode::MathOverflow)?;
+
+    let position = borrower.position_mut(&collateral_bank.key())?;
+    position.deposited_shares = position.deposited_shares.checked_sub(seized_shares).ok_or(ErrorCode::MathOverflow)?;
+
+    collateral_bank.total_deposits = collateral_bank.total_deposits.checked_sub(seized_amount).ok_or(ErrorCode::MathOverflow)?;
+    collateral_bank.total_deposit_shares = collateral_bank.total_deposit_shares.checked_sub(seized_shares).ok_or(ErrorCode::MathOverflow)?;
+    collateral_bank.protocol_fees = collateral_bank.protocol_fees.checked_add(protocol_fee).ok_or(ErrorCode::MathOverflow)?;
+
+    // Repaid debt no longer counts against the ceiling of the isolated collateral backing it
+    if isolated_debt > 0 {
+        let release = |isolated_bank: &mut Bank| {
+            isolated_bank.remove_isolated_debt(isolated_debt);
+            Ok(())
+        };
+        if borrower.isolated_bank == borrowed_bank.key() {
+            release(borrowed_bank)?;
+        } else {
+            update_isolated_bank(borrower.isolated_bank, collateral_bank, ctx.remaining_accounts, release)?;
+        }
+    }
+
+    let loaded_banks = [
+        (collateral_bank.key(), &**collateral_bank),
+        (borrowed_bank.key(), &**borrowed_bank),
+    ];
+    let health = compute_health(borrower, &ctx.accounts.protocol_config, &loaded_banks, ctx.remaining_accounts, HealthType::Maintenance)?;
+    borrower.health_factor = health.factor()?.to_scaled_val()?;
+    if health.is_healthy() {
+        borrower.liquidatable_since = 0;
+    }
+
+    emit!(LiquidationEvent {
+        liquidator: ctx.accounts.liquidator.key(),
+        borrower: borrower.owner,
+        collateral_bank: collateral_bank.key(),
+        borrowed_bank: borrowed_bank.key(),
+        repay_amount: repaid_amount,
+        repaid_shares,
+        seized_amount,
+        seized_shares,
+        liquidation_bonus,
+        protocol_fee,
+        collateral_price: collateral_price.to_scaled_val()?,
+        borrowed_price: borrowed_price.to_scaled_val()?,
+        health_factor_before,
+        health_factor_after: borrower.health_factor,
+        collateral_total_deposits: collateral_bank.total_deposits,
+        borrowed_total_borrowed: borrowed_bank.total_borrowed,
+        timestamp: now,
+    });
+
+    // Transfer seized collateral, including the bonus minus the protocol's cut, to liquidator
     let transfer_to_liquidator = TransferChecked {
         from: ctx.accounts.collateral_bank_token_account.to_account_info(),
         mint: ctx.accounts.collateral_mint.to_account_info(),
@@ -128,9 +256,10 @@ pub fn process_liquidate(ctx: Context<Liquidate>) -> Result<()> {
             &[ctx.bumps.collateral_bank_token_account],
         ],
     ];
-    let cpi_ctx_to_liquidator = CpiContext::new(cpi_program.clone(), transfer_to_liquidator).with_signer(signer_seeds);
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx_to_liquidator = CpiContext::new(cpi_program, transfer_to_liquidator).with_signer(signer_seeds);
     let collateral_decimals = ctx.accounts.collateral_mint.decimals;   
-    token_interface::transfer_checked(cpi_ctx_to_liquidator, liquidation_bonus, collateral_decimals)?;
+    token_interface::transfer_checked(cpi_ctx_to_liquidator, liquidator_amount, collateral_decimals)?;
 
     Ok(())
-}
\ No newline at end of file
+}
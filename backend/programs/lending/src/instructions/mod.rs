@@ -1,5 +1,7 @@
 pub use admin::*;
 pub mod admin;
+pub use protocol::*;
+pub mod protocol;
 pub use deposit::*;
 pub mod deposit;
 pub use borrow::*;
@@ -9,4 +11,16 @@ pub mod withdraw;
 pub use repay::*;
 pub mod repay;
 pub use liquidate::*;
-pub mod liquidate;
\ No newline at end of file
+pub mod liquidate;
+pub use flash_loan::*;
+pub mod flash_loan;
+pub use collect_fees::*;
+pub mod collect_fees;
+pub use set_collateral::*;
+pub mod set_collateral;
+pub use set_user_emode::*;
+pub mod set_user_emode;
+pub use handle_bad_debt::*;
+pub mod handle_bad_debt;
+pub use update_user_health::*;
+pub mod update_user_health;
\ No newline at end of file
@@ -0,0 +1,211 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_2022::spl_token_2022::extension::ExtensionType;
+use crate::constants::{BPS_SCALE, MAX_ALLOWED_MINT_EXTENSIONS, MAX_EMODE_CATEGORIES, MAX_LIQUIDATION_BONUS};
+use crate::events::{
+    AdminAccepted, AdminProposed, BankPauseSet, EModeCategorySet, MintExtensionsAllowed, ProtocolConfigInitialized,
+    ProtocolPauseSet, ProtocolRolesUpdated,
+};
+use crate::instructions::admin::validate_oracle_config;
+use crate::state::*;
+use crate::error::ErrorCode;
+
+// Only the program's upgrade authority can initialize the config, so nobody can front-run the deployment
+#[derive(Accounts)]
+pub struct InitProtocolConfig<'info> {
+    #[account(mut)]
+    pub signer: Signer<'info>,
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()) @ ErrorCode::Unauthorized)]
+    pub program: Program<'info, crate::program::LendingProtocol>,
+    #[account(constraint = program_data.upgrade_authority_address == Some(signer.key()) @ ErrorCode::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
+    #[account(
+        init, 
+        space = 8 + ProtocolConfig::INIT_SPACE, 
+        payer = signer,
+        seeds = [b"protocol_config"],
+        bump, 
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    pub system_program: Program <'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateProtocolConfig<'info> {
+    pub admin: Signer<'info>,
+    #[account(
+        mut, 
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+        has_one = admin @ ErrorCode::Unauthorized,
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    pub pending_admin: Signer<'info>,
+    #[account(
+        mut, 
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+        has_one = pending_admin @ ErrorCode::Unauthorized,
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetProtocolPause<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        mut, 
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+        constraint = signer.key() == protocol_config.guardian || signer.key() == protocol_config.admin @ ErrorCode::Unauthorized,
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetBankPause<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+        constraint = signer.key() == protocol_config.guardian || signer.key() == protocol_config.admin @ ErrorCode::Unauthorized,
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(mut)]
+    pub bank: Account<'info, Bank>,
+}
+
+// The upgrade authority becomes admin and is expected to hand the role to the protocol multisig via propose_admin
+pub fn process_init_protocol_config(ctx: Context<InitProtocolConfig>, guardian: Pubkey, fee_recipient: Pubkey) -> Result<()> {
+    let protocol_config = &mut ctx.accounts.protocol_config;
+    protocol_config.admin = ctx.accounts.signer.key();
+    protocol_config.pending_admin = Pubkey::default();
+    protocol_config.guardian = guardian;
+    protocol_config.fee_recipient = fee_recipient;
+    protocol_config.bump = ctx.bumps.protocol_config;
+
+    emit!(ProtocolConfigInitialized {
+        admin: protocol_config.admin,
+        guardian,
+        fee_recipient,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+pub fn process_set_protocol_roles(ctx: Context<UpdateProtocolConfig>, guardian: Pubkey, fee_recipient: Pubkey) -> Result<()> {
+    let protocol_config = &mut ctx.accounts.protocol_config;
+    protocol_config.guardian = guardian;
+    protocol_config.fee_recipient = fee_recipient;
+
+    emit!(ProtocolRolesUpdated {
+        guardian,
+        fee_recipient,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+// Admin transfer is two-step so a typo in the new admin address cannot lock the protocol
+pub fn process_propose_admin(ctx: Context<UpdateProtocolConfig>, new_admin: Pubkey) -> Result<()> {
+    let protocol_config = &mut ctx.accounts.protocol_config;
+    protocol_config.pending_admin = new_admin;
+
+    emit!(AdminProposed {
+        admin: protocol_config.admin,
+        pending_admin: new_admin,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+pub fn process_accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+    let protocol_config = &mut ctx.accounts.protocol_config;
+    require_keys_neq!(protocol_config.pending_admin, Pubkey::default(), ErrorCode::Unauthorized);
+
+    let old_admin = protocol_config.admin;
+    protocol_config.admin = protocol_config.pending_admin;
+    protocol_config.pending_admin = Pubkey::default();
+
+    emit!(AdminAccepted {
+        old_admin,
+        new_admin: protocol_config.admin,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+// Only affects banks listed afterwards, existing banks keep accepting their mint
+pub fn process_set_allowed_mint_extensions(ctx: Context<UpdateProtocolConfig>, extensions: Vec<u16>) -> Result<()> {
+    require!(extensions.len() <= MAX_ALLOWED_MINT_EXTENSIONS, ErrorCode::InvalidMintExtensionAllowlist);
+    for extension in &extensions {
+        require!(ExtensionType::try_from(*extension).is_ok(), ErrorCode::InvalidMintExtensionAllowlist);
+    }
+
+    ctx.accounts.protocol_config.allowed_mint_extensions = extensions.clone();
+
+    emit!(MintExtensionsAllowed {
+        extensions,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+// Adds the category or replaces the one with the same ID. Categories cannot be removed since banks and
+// users keep referring to them by ID.
+pub fn process_set_emode_category(ctx: Context<UpdateProtocolConfig>, category: EModeCategory) -> Result<()> {
+    require!(category.id != 0, ErrorCode::InvalidEModeCategory);
+    require!(category.max_ltv < category.liquidation_threshold, ErrorCode::InvalidEModeCategory);
+    require!(category.liquidation_threshold < BPS_SCALE, ErrorCode::InvalidEModeCategory);
+    require!(category.liquidation_bonus <= MAX_LIQUIDATION_BONUS, ErrorCode::InvalidEModeCategory);
+    if let Some(oracle) = &category.oracle {
+        validate_oracle_config(oracle)?;
+    }
+
+    let categories = &mut ctx.accounts.protocol_config.emode_categories;
+    match categories.iter_mut().find(|existing| existing.id == category.id) {
+        Some(existing) => *existing = category,
+        None => {
+            require!(categories.len() < MAX_EMODE_CATEGORIES, ErrorCode::InvalidEModeCategory);
+            categories.push(category);
+        }
+    }
+
+    emit!(EModeCategorySet {
+        category,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+pub fn process_set_protocol_pause(ctx: Context<SetProtocolPause>, paused: PauseFlags) -> Result<()> {
+    ctx.accounts.protocol_config.paused = paused;
+
+    emit!(ProtocolPauseSet {
+        paused,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
+
+pub fn process_set_bank_pause(ctx: Context<SetBankPause>, paused: PauseFlags) -> Result<()> {
+    ctx.accounts.bank.paused = paused;
+
+    emit!(BankPauseSet {
+        bank: ctx.accounts.bank.key(),
+        paused,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    Ok(())
+}
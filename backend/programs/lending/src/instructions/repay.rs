@@ -1,13 +1,24 @@
 use anchor_lang::prelude::*;
 use anchor_spl::associated_token::AssociatedToken;
-use anchor_spl::token_interface::{ self, Mint, TokenAccount, TokenInterface, TransferChecked };
+use anchor_spl::token_interface::{ Mint, TokenAccount, TokenInterface };
+use crate::interest::accrue_bank_interest;
+use crate::transfer::transfer_in;
+use crate::isolation::update_isolated_bank;
+use crate::math::Rounding;
+use crate::events::RepayEvent;
 use crate::state::*;
 use crate::error::ErrorCode;
 
+// Remaining accounts: the user's isolated bank, writable, while the repaid position has debt counted against its ceiling
 #[derive(Accounts)]
 pub struct Repay<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
     pub mint: InterfaceAccount<'info, Mint>,
     #[account(
         mut, 
@@ -41,65 +52,69 @@ pub struct Repay<'info> {
 }
 
 // Repay function just needs to make a CPI transfer from the user's token account into the bank's token account
-pub fn process_repay(ctx: Context<Repay>, amount: u64) -> Result<()> {
-    let user = &mut ctx.accounts.user_account;
+pub fn process_repay<'info>(ctx: Context<'_, '_, 'info, 'info, Repay<'info>>, amount: u64) -> Result<()> {
+    ctx.accounts.protocol_config.require_not_paused(&[&ctx.accounts.bank], Operation::Repay)?;
 
-    let borrowed_asset; 
+    let now = Clock::get()?.unix_timestamp;
+    accrue_bank_interest(&mut ctx.accounts.bank, now)?;
 
-    // Note: For simplicity, interest fees are not included in this calculation
+    let user = &ctx.accounts.user_account;
+    let bank = &ctx.accounts.bank;
 
-    match ctx.accounts.mint.to_account_info().key() {
-        key if key == user.usdc_address => {
-            borrowed_asset = user.borrowed_usdc;
-        },
-        _ => {
-            borrowed_asset = user.borrowed_sol;
-        }
-    }
+    // Note: Share math goes through `Decimal`, which returns `MathOverflow` instead of panicking and lets
+    // each step choose its rounding direction. Debt owed rounds up, debt shares removed from the user round down.
+    let borrowed_shares = user.position(&bank.key()).map_or(0, |position| position.borrowed_shares);
+    let borrowed_asset = bank.borrow_amount(borrowed_shares, Rounding::Up)?;
 
     if amount > borrowed_asset {
         return Err(ErrorCode::OverRepay.into());
     }
 
-    let transfer_cpi_accounts = TransferChecked {
-        from: ctx.accounts.user_token_account.to_account_info(),
-        mint: ctx.accounts.mint.to_account_info(),
-        to: ctx.accounts.bank_token_account.to_account_info(),
-        authority: ctx.accounts.signer.to_account_info(),
+    let received = transfer_in(
+        &ctx.accounts.token_program,
+        &ctx.accounts.mint,
+        &ctx.accounts.user_token_account,
+        &mut ctx.accounts.bank_token_account,
+        &ctx.accounts.signer,
+        amount,
+    )?;
+
+    let users_shares = if received == borrowed_asset {
+        borrowed_shares
+    } else {
+        ctx.accounts.bank.borrow_shares(received, Rounding::Down)?
     };
 
-    let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_ctx = CpiContext::new(cpi_program, transfer_cpi_accounts);
-    let decimals = ctx.accounts.mint.decimals;
+    let bank = &mut ctx.accounts.bank;
+    let user = &mut ctx.accounts.user_account;
 
-    token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
+    let position = user.position_mut(&bank.key())?;
+    let isolated_debt = position.release_isolated_debt(users_shares)?;
 
-    // Note: The checked_ prefix in Rust is used to perform operations safely by checking for potential 
-    // arithmetic overflow or other errors that could occur during the computation. If such an error occurs, these methods
-    // return None instead of causing a panic.
+    position.borrowed_shares = position.borrowed_shares.checked_sub(users_shares).ok_or(ErrorCode::MathOverflow)?;
 
-    let bank = &mut ctx.accounts.bank;
+    // Add in "update health factor" function here
 
-    let borrowed_ratio = amount.checked_div(bank.total_borrowed).unwrap();
-    let users_shares = bank.total_borrowed_shares.checked_mul(borrowed_ratio).unwrap();
-    
-    let user = &mut ctx.accounts.user_account;
-    
-    match ctx.accounts.mint.to_account_info().key() {
-        key if key == user.usdc_address => {
-            user.borrowed_usdc -= amount;
-            user.borrowed_usdc_shares -= users_shares;
-        },
-        _ => {
-            user.borrowed_sol -= amount;
-            user.borrowed_sol_shares -= users_shares; 
-        }
-    }
+    // Individual debts round up, so their sum can exceed the bank's total by a few units
+    bank.total_borrowed = bank.total_borrowed.saturating_sub(received);
+    bank.total_borrowed_shares = bank.total_borrowed_shares.checked_sub(users_shares).ok_or(ErrorCode::MathOverflow)?;
 
-    // Add in "update health factor" function here
+    if isolated_debt > 0 {
+        update_isolated_bank(user.isolated_bank, bank, ctx.remaining_accounts, |isolated_bank| {
+            isolated_bank.remove_isolated_debt(isolated_debt);
+            Ok(())
+        })?;
+    }
 
-    bank.total_borrowed -= amount;
-    bank.total_borrowed_shares -= users_shares;
+    emit!(RepayEvent {
+        user: user.owner,
+        bank: bank.key(),
+        amount: received,
+        shares: users_shares,
+        total_borrowed: bank.total_borrowed,
+        total_borrowed_shares: bank.total_borrowed_shares,
+        timestamp: now,
+    });
 
     Ok(())
 }
\ No newline at end of file
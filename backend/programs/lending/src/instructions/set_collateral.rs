@@ -0,0 +1,66 @@
+use anchor_lang::prelude::*;
+use crate::health::{compute_health, HealthType};
+use crate::events::CollateralToggled;
+use crate::state::*;
+use crate::error::ErrorCode;
+
+// Remaining accounts: a [bank, price update] pair for every open position of the user, see `compute_health`
+#[derive(Accounts)]
+pub struct SetCollateral<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    pub bank: Account<'info, Bank>,
+    #[account(
+        mut, 
+        seeds = [signer.key().as_ref()],
+        bump,
+    )]  
+    pub user_account: Account<'info, User>,
+}
+
+// 1. When enabling, check the bank can be combined with the user's other collateral under isolation mode
+// 2. Update the collateral flag of the user's position in the bank
+// 3. When disabling, check the user's remaining collateral still covers their debt at max LTV
+
+pub fn process_set_collateral<'info>(ctx: Context<'_, '_, 'info, 'info, SetCollateral<'info>>, is_collateral: bool) -> Result<()> {
+    let bank = &ctx.accounts.bank;
+    let bank_key = bank.key();
+    let user = &mut ctx.accounts.user_account;
+
+    if is_collateral {
+        require!(user.can_use_as_collateral(&bank_key, bank.isolated), ErrorCode::IsolationModeViolation);
+        if bank.isolated {
+            user.isolated_bank = bank_key;
+        }
+    }
+
+    let position = user.position_mut(&bank_key)?;
+    position.is_collateral = is_collateral;
+
+    // Enabling collateral can only improve health, so only disabling needs the user's prices
+    if !is_collateral {
+        let health = compute_health(user, &ctx.accounts.protocol_config, &[], ctx.remaining_accounts, HealthType::Initial)?;
+        if !health.is_healthy() {
+            return Err(ErrorCode::UnderCollateralized.into());
+        }
+        user.health_factor = health.factor()?.to_scaled_val()?;
+        user.liquidatable_since = 0;
+    }
+
+    let now = Clock::get()?.unix_timestamp;
+    user.last_updated = now;
+
+    emit!(CollateralToggled {
+        user: user.owner,
+        bank: bank_key,
+        is_collateral,
+        health_factor: user.health_factor,
+        timestamp: now,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use anchor_lang::prelude::*;
+use crate::health::{compute_health, HealthType};
+use crate::events::UserEModeSet;
+use crate::state::*;
+use crate::error::ErrorCode;
+
+// Remaining accounts: a [bank, price update] pair for every open position of the user, see `compute_health`
+#[derive(Accounts)]
+pub struct SetUserEMode<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(
+        mut, 
+        seeds = [signer.key().as_ref()],
+        bump,
+    )]  
+    pub user_account: Account<'info, User>,
+}
+
+// 1. Check the category exists, 0 leaves e-mode
+// 2. Update the user's category
+// 3. Check the user's collateral still covers their debt at max LTV under the new parameters
+
+pub fn process_set_user_emode<'info>(ctx: Context<'_, '_, 'info, 'info, SetUserEMode<'info>>, emode_category: u8) -> Result<()> {
+    let protocol_config = &ctx.accounts.protocol_config;
+    require!(
+        emode_category == 0 || protocol_config.emode_category(emode_category).is_some(),
+        ErrorCode::InvalidEModeCategory
+    );
+
+    let user = &mut ctx.accounts.user_account;
+    user.emode_category = emode_category;
+
+    let health = compute_health(user, protocol_config, &[], ctx.remaining_accounts, HealthType::Initial)?;
+    if !health.is_healthy() {
+        return Err(ErrorCode::UnderCollateralized.into());
+    }
+    user.health_factor = health.factor()?.to_scaled_val()?;
+    user.liquidatable_since = 0;
+
+    let now = Clock::get()?.unix_timestamp;
+    user.last_updated = now;
+
+    emit!(UserEModeSet {
+        user: user.owner,
+        emode_category,
+        health_factor: user.health_factor,
+        timestamp: now,
+    });
+
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use anchor_lang::prelude::*;
+use crate::health::{compute_health, HealthType};
+use crate::events::UserHealthUpdated;
+use crate::state::*;
+
+// Anyone can refresh a user's health. Keepers use it to start the liquidation bonus clock as soon as a user
+// becomes liquidatable, users to clear it once prices have moved back in their favour.
+// Remaining accounts: a [bank, price update] pair for every open position of the user, see `compute_health`
+#[derive(Accounts)]
+pub struct UpdateUserHealth<'info> {
+    pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+    #[account(mut)]
+    pub user_account: Account<'info, User>,
+}
+
+// 1. Compute the user's health against the liquidation threshold
+// 2. Record when the user became liquidatable, or clear it if they are healthy
+
+pub fn process_update_user_health<'info>(ctx: Context<'_, '_, 'info, 'info, UpdateUserHealth<'info>>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let user = &mut ctx.accounts.user_account;
+
+    let health = compute_health(user, &ctx.accounts.protocol_config, &[], ctx.remaining_accounts, HealthType::Maintenance)?;
+    user.health_factor = health.factor()?.to_scaled_val()?;
+
+    if health.is_healthy() {
+        user.liquidatable_since = 0;
+    } else if user.liquidatable_since == 0 {
+        user.liquidatable_since = now;
+    }
+
+    emit!(UserHealthUpdated {
+        user: user.owner,
+        health_factor: user.health_factor,
+        liquidatable_since: user.liquidatable_since,
+        timestamp: now,
+    });
+
+    Ok(())
+}
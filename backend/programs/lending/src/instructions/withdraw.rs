@@ -1,13 +1,23 @@
 use anchor_lang::prelude::*;
 use anchor_spl::associated_token::AssociatedToken;
 use anchor_spl::token_interface::{ self, Mint, TokenAccount, TokenInterface, TransferChecked };
+use crate::health::{compute_health, HealthType};
+use crate::interest::accrue_bank_interest;
+use crate::math::Rounding;
+use crate::events::WithdrawEvent;
 use crate::state::*;
 use crate::error::ErrorCode;
 
+// Remaining accounts: a [bank, price update] pair for every open position of the user, see `compute_health`
 #[derive(Accounts)]
 pub struct Withdraw<'info> {
     #[account(mut)]
     pub signer: Signer<'info>,
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
     pub mint: InterfaceAccount<'info, Mint>,
     #[account(
         mut, 
@@ -40,28 +50,58 @@ pub struct Withdraw<'info> {
     pub system_program: Program<'info, System>,
 }
 
-// 1. CPI transfer from bank's token account to user's token account
-// 2. Calculate new shares to be removed from the bank
-// 3. Update user's deposited amount and total collateral value
-// 4. Update bank's total deposits and total deposit shares
-// 5. Update users health factor ??
+// 1. Calculate shares to be burned at the bank's current exchange rate
+// 2. Update user's deposit shares
+// 3. Update bank's total deposits and total deposit shares
+// 4. Check the user's remaining collateral still covers their debt at max LTV
+// 5. CPI transfer from bank's token account to user's token account
 
-pub fn process_withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
-    let user = &mut ctx.accounts.user_account;
+pub fn process_withdraw<'info>(ctx: Context<'_, '_, 'info, 'info, Withdraw<'info>>, amount: u64) -> Result<()> {
+    ctx.accounts.protocol_config.require_not_paused(&[&ctx.accounts.bank], Operation::Withdraw)?;
+    require!(ctx.accounts.bank.flash_loan_amount == 0, ErrorCode::FlashLoanInProgress);
 
-    let deposited_value; 
+    let now = Clock::get()?.unix_timestamp;
+    accrue_bank_interest(&mut ctx.accounts.bank, now)?;
 
-    // FIXME: Change from if statement to match statement?? Use PDA deserialization to get the mint address??
-    if ctx.accounts.mint.to_account_info().key() == user.usdc_address {
-        deposited_value = user.deposited_usdc;
-    } else {
-        deposited_value = user.deposited_sol;
-    }
+    let user = &ctx.accounts.user_account;
+    let bank = &ctx.accounts.bank;
+
+    // Shares burned from the user always round up
+    let deposited_shares = user.position(&bank.key()).map_or(0, |position| position.deposited_shares);
+    let shares_to_remove = bank.deposit_shares(amount, Rounding::Up)?;
 
-    if amount > deposited_value {
+    if shares_to_remove > deposited_shares {
         return Err(ErrorCode::InsufficientFunds.into());
     }
 
+    let bank = &mut ctx.accounts.bank;
+    let user = &mut ctx.accounts.user_account;
+    let position = user.position_mut(&bank.key())?;
+
+    position.deposited_shares = position.deposited_shares.checked_sub(shares_to_remove).ok_or(ErrorCode::MathOverflow)?;
+
+    bank.total_deposits = bank.total_deposits.checked_sub(amount).ok_or(ErrorCode::MathOverflow)?;
+    bank.total_deposit_shares = bank.total_deposit_shares.checked_sub(shares_to_remove).ok_or(ErrorCode::MathOverflow)?;
+
+    let health = compute_health(user, &ctx.accounts.protocol_config, &[(bank.key(), bank)], ctx.remaining_accounts, HealthType::Initial)?;
+    if !health.is_healthy() {
+        return Err(ErrorCode::UnderCollateralized.into());
+    }
+    user.health_factor = health.factor()?.to_scaled_val()?;
+    user.liquidatable_since = 0;
+
+    emit!(WithdrawEvent {
+        user: user.owner,
+        bank: bank.key(),
+        amount,
+        shares: shares_to_remove,
+        total_deposits: bank.total_deposits,
+        total_deposit_shares: bank.total_deposit_shares,
+        price: health.price(&bank.key()).ok().map(|price| price.to_scaled_val()).transpose()?,
+        health_factor: user.health_factor,
+        timestamp: now,
+    });
+
     let transfer_cpi_accounts = TransferChecked {
         from: ctx.accounts.bank_token_account.to_account_info(),
         mint: ctx.accounts.mint.to_account_info(),
@@ -84,19 +124,5 @@ pub fn process_withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
 
     token_interface::transfer_checked(cpi_ctx, amount, decimals)?;
 
-    let bank = &mut ctx.accounts.bank;
-    let shares_to_remove = (amount as f64 / bank.total_deposits as f64) * bank.total_deposit_shares as f64;
-
-    let user = &mut ctx.accounts.user_account;
-    
-    if ctx.accounts.mint.to_account_info().key() == user.usdc_address {
-        user.deposited_usdc -= shares_to_remove as u64;
-    } else {
-        user.deposited_sol -= shares_to_remove as u64;
-    }
-
-    bank.total_deposits -= amount;
-    bank.total_deposit_shares -= shares_to_remove as u64;
-    
     Ok(())    
 }
\ No newline at end of file
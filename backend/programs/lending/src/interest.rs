@@ -0,0 +1,98 @@
+use anchor_lang::prelude::*;
+use crate::constants::SECONDS_PER_YEAR;
+use crate::math::{compound_interest, Decimal, Rounding};
+use crate::events::InterestAccrued;
+use crate::state::Bank;
+
+/// Share of the bank's deposits that is currently borrowed
+pub fn utilization(bank: &Bank) -> Result<Decimal> {
+    if bank.total_deposits == 0 {
+        return Ok(Decimal::zero());
+    }
+
+    let utilization = Decimal::from_ratio(bank.total_borrowed, bank.total_deposits, Rounding::Down)?;
+    Ok(utilization.min(Decimal::one()))
+}
+
+/// Annual borrow rate for the given utilization
+pub fn borrow_rate(bank: &Bank, utilization: Decimal) -> Result<Decimal> {
+    let config = &bank.interest_rate;
+    let base_rate = Decimal::from_bps(config.base_rate);
+    let slope1 = Decimal::from_bps(config.slope1);
+    let slope2 = Decimal::from_bps(config.slope2);
+    let optimal_utilization = Decimal::from_bps(config.optimal_utilization);
+
+    if utilization <= optimal_utilization {
+        let slope = slope1
+            .try_mul(utilization, Rounding::Down)?
+            .try_div(optimal_utilization, Rounding::Down)?;
+        base_rate.try_add(slope)
+    } else {
+        let excess = utilization.try_sub(optimal_utilization)?;
+        let range = Decimal::one().try_sub(optimal_utilization)?;
+        let slope = slope2
+            .try_mul(excess, Rounding::Down)?
+            .try_div(range, Rounding::Down)?;
+        base_rate.try_add(slope1)?.try_add(slope)
+    }
+}
+
+/// Moves the bank's borrows and deposits forward to `now`.
+///
+/// Borrowers are charged the interest for the elapsed period. The reserve factor's share of it goes to
+/// `protocol_fees` and depositors are credited the rest, so the value of both deposit and borrow shares grows over time.
+pub fn accrue_interest(bank: &mut Bank, now: i64) -> Result<()> {
+    let elapsed = now.saturating_sub(bank.last_updated);
+    if elapsed <= 0 {
+        return Ok(());
+    }
+    bank.last_updated = now;
+
+    if bank.total_borrowed == 0 {
+        return Ok(());
+    }
+
+    let rate_per_second = borrow_rate(bank, utilization(bank)?)?
+        .try_div(Decimal::from(SECONDS_PER_YEAR), Rounding::Down)?;
+    let growth = compound_interest(rate_per_second, elapsed as u64)?;
+
+    let total_borrowed = Decimal::from(bank.total_borrowed);
+    let interest = total_borrowed
+        .try_mul(growth, Rounding::Up)?
+        .try_sub(total_borrowed)?;
+    let reserve = interest.try_mul(Decimal::from_bps(bank.interest_rate.reserve_factor), Rounding::Up)?;
+    let depositor_interest = interest.try_sub(reserve)?;
+
+    bank.total_borrowed = total_borrowed.try_add(interest)?.to_u64(Rounding::Up)?;
+    bank.protocol_fees = Decimal::from(bank.protocol_fees)
+        .try_add(reserve)?
+        .to_u64(Rounding::Down)?;
+    bank.total_deposits = Decimal::from(bank.total_deposits)
+        .try_add(depositor_interest)?
+        .to_u64(Rounding::Down)?;
+
+    Ok(())
+}
+
+/// Accrues interest on a bank account the instruction will write back and logs what was accrued
+pub fn accrue_bank_interest(bank: &mut Account<Bank>, now: i64) -> Result<()> {
+    let total_deposits = bank.total_deposits;
+    let total_borrowed = bank.total_borrowed;
+    let protocol_fees = bank.protocol_fees;
+
+    accrue_interest(bank, now)?;
+
+    if bank.total_borrowed != total_borrowed {
+        emit!(InterestAccrued {
+            bank: bank.key(),
+            borrow_interest: bank.total_borrowed - total_borrowed,
+            deposit_interest: bank.total_deposits - total_deposits,
+            protocol_fee: bank.protocol_fees - protocol_fees,
+            total_deposits: bank.total_deposits,
+            total_borrowed: bank.total_borrowed,
+            timestamp: now,
+        });
+    }
+
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use anchor_lang::prelude::*;
+use crate::constants::DEBT_CEILING_DECIMALS;
+use crate::math::{Decimal, Rounding};
+use crate::state::Bank;
+use crate::error::ErrorCode;
+
+/// Value of `amount` base units at `price` as counted against an isolated bank's debt ceiling, rounded up
+pub fn isolated_debt_value(amount: u64, price: Decimal) -> Result<u64> {
+    Decimal::from(amount)
+        .try_mul(price, Rounding::Up)?
+        .try_mul(Decimal::pow10(DEBT_CEILING_DECIMALS)?, Rounding::Up)?
+        .to_u64(Rounding::Up)
+}
+
+/// Applies `update` to the user's isolated collateral bank and writes it back.
+///
+/// The isolated bank is either the instruction's own `bank` or one of the banks in `remaining_accounts`,
+/// which must then be passed as writable.
+pub fn update_isolated_bank<'info>(
+    isolated_bank: Pubkey,
+    bank: &mut Account<'info, Bank>,
+    remaining_accounts: &'info [AccountInfo<'info>],
+    update: impl FnOnce(&mut Bank) -> Result<()>,
+) -> Result<()> {
+    if isolated_bank == bank.key() {
+        return update(bank);
+    }
+
+    let isolated_bank_info = remaining_accounts
+        .iter()
+        .find(|account| account.key() == isolated_bank)
+        .ok_or(ErrorCode::InvalidRemainingAccounts)?;
+    let mut isolated_bank = Account::<Bank>::try_from(isolated_bank_info)?;
+    update(&mut isolated_bank)?;
+    isolated_bank.exit(&crate::ID)
+}
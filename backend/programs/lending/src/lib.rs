@@ -1,10 +1,18 @@
 use anchor_lang::prelude::*;
 use instructions::*;
+use state::{BankConfig, EModeCategory, OracleConfig, PauseFlags};
 
 mod state;
 mod instructions;
 mod error;
 mod constants;
+mod oracle;
+mod interest;
+mod math;
+mod health;
+mod events;
+mod isolation;
+mod transfer;
 
 declare_id!("FDGDjSxbZ4QanUBA2CpajDzKpusS4TvhzS2VyoKmjnyn");
 
@@ -13,32 +21,104 @@ pub mod lending_protocol {
 
     use super::*;
 
-    pub fn init_bank(ctx: Context<InitBank>, liquidation_threshold: u64, max_ltv: u64) -> Result<()> {
-        process_init_bank(ctx, liquidation_threshold, max_ltv)
+    pub fn init_protocol_config(ctx: Context<InitProtocolConfig>, guardian: Pubkey, fee_recipient: Pubkey) -> Result<()> {
+        process_init_protocol_config(ctx, guardian, fee_recipient)
     }
 
-    pub fn init_user(ctx: Context<InitUser>, usdc_address: Pubkey) -> Result<()> {
-        process_init_user(ctx, usdc_address)
+    pub fn set_protocol_roles(ctx: Context<UpdateProtocolConfig>, guardian: Pubkey, fee_recipient: Pubkey) -> Result<()> {
+        process_set_protocol_roles(ctx, guardian, fee_recipient)
+    }
+
+    pub fn propose_admin(ctx: Context<UpdateProtocolConfig>, new_admin: Pubkey) -> Result<()> {
+        process_propose_admin(ctx, new_admin)
+    }
+
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        process_accept_admin(ctx)
+    }
+
+    pub fn set_allowed_mint_extensions(ctx: Context<UpdateProtocolConfig>, extensions: Vec<u16>) -> Result<()> {
+        process_set_allowed_mint_extensions(ctx, extensions)
+    }
+
+    pub fn set_emode_category(ctx: Context<UpdateProtocolConfig>, category: EModeCategory) -> Result<()> {
+        process_set_emode_category(ctx, category)
+    }
+
+    pub fn set_protocol_pause(ctx: Context<SetProtocolPause>, paused: PauseFlags) -> Result<()> {
+        process_set_protocol_pause(ctx, paused)
+    }
+
+    pub fn set_bank_pause(ctx: Context<SetBankPause>, paused: PauseFlags) -> Result<()> {
+        process_set_bank_pause(ctx, paused)
+    }
+
+    pub fn init_bank(ctx: Context<InitBank>, config: BankConfig, oracle: OracleConfig) -> Result<()> {
+        process_init_bank(ctx, config, oracle)
+    }
+
+    pub fn update_bank_config(ctx: Context<UpdateBankConfig>, config: BankConfig) -> Result<()> {
+        process_update_bank_config(ctx, config)
+    }
+
+    pub fn update_bank_oracle(ctx: Context<UpdateBankOracle>, oracle: OracleConfig) -> Result<()> {
+        process_update_bank_oracle(ctx, oracle)
+    }
+
+    pub fn update_bank_fallback_oracle(ctx: Context<UpdateBankOracle>, fallback_oracle: Option<OracleConfig>, max_oracle_deviation: u64) -> Result<()> {
+        process_update_bank_fallback_oracle(ctx, fallback_oracle, max_oracle_deviation)
+    }
+
+    pub fn init_user(ctx: Context<InitUser>) -> Result<()> {
+        process_init_user(ctx)
     }
 
     pub fn deposit (ctx: Context<Deposit>, amount: u64) -> Result<()> {
         process_deposit(ctx, amount)
     }
 
-    pub fn withdraw (ctx: Context<Withdraw>, amount: u64) -> Result<()> {
+    pub fn withdraw<'info>(ctx: Context<'_, '_, 'info, 'info, Withdraw<'info>>, amount: u64) -> Result<()> {
         process_withdraw(ctx, amount)
     }
 
-    pub fn borrow(ctx: Context<Borrow>, amount: u64) -> Result<()> {
+    pub fn borrow<'info>(ctx: Context<'_, '_, 'info, 'info, Borrow<'info>>, amount: u64) -> Result<()> {
         process_borrow(ctx, amount)
     }
 
-    pub fn repay(ctx: Context<Repay>, amount: u64) -> Result<()> {
+    pub fn repay<'info>(ctx: Context<'_, '_, 'info, 'info, Repay<'info>>, amount: u64) -> Result<()> {
         process_repay(ctx, amount)
     }
 
-    pub fn liquidate(ctx: Context<Liquidate>) -> Result<()> {
-        process_liquidate(ctx)
+    pub fn liquidate<'info>(ctx: Context<'_, '_, 'info, 'info, Liquidate<'info>>, repay_amount: u64) -> Result<()> {
+        process_liquidate(ctx, repay_amount)
+    }
+
+    pub fn set_collateral<'info>(ctx: Context<'_, '_, 'info, 'info, SetCollateral<'info>>, is_collateral: bool) -> Result<()> {
+        process_set_collateral(ctx, is_collateral)
+    }
+
+    pub fn set_user_emode<'info>(ctx: Context<'_, '_, 'info, 'info, SetUserEMode<'info>>, emode_category: u8) -> Result<()> {
+        process_set_user_emode(ctx, emode_category)
+    }
+
+    pub fn flash_borrow(ctx: Context<FlashBorrow>, amount: u64) -> Result<()> {
+        process_flash_borrow(ctx, amount)
+    }
+
+    pub fn flash_repay(ctx: Context<FlashRepay>) -> Result<()> {
+        process_flash_repay(ctx)
+    }
+
+    pub fn collect_fees(ctx: Context<CollectFees>) -> Result<()> {
+        process_collect_fees(ctx)
+    }
+
+    pub fn update_user_health<'info>(ctx: Context<'_, '_, 'info, 'info, UpdateUserHealth<'info>>) -> Result<()> {
+        process_update_user_health(ctx)
+    }
+
+    pub fn handle_bad_debt<'info>(ctx: Context<'_, '_, 'info, 'info, HandleBadDebt<'info>>) -> Result<()> {
+        process_handle_bad_debt(ctx)
     }
 }
 
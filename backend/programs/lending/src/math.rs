@@ -0,0 +1,235 @@
+use anchor_lang::prelude::*;
+use crate::constants::BPS_SCALE;
+use crate::error::ErrorCode;
+use self::uint_types::U192;
+
+// Kept in its own module so the macro's `Result` does not resolve to anchor's
+#[allow(clippy::assign_op_pattern, clippy::manual_div_ceil)]
+mod uint_types {
+    use uint::construct_uint;
+
+    construct_uint! {
+        /// 192-bit unsigned integer, wide enough to multiply two WAD scaled values without overflowing
+        pub struct U192(3);
+    }
+}
+
+/// Scale of a `Decimal`, 18 decimal places
+pub const WAD: u128 = 1_000_000_000_000_000_000;
+
+/// Direction to round in when a result cannot be represented exactly.
+///
+/// Amounts credited to a user (shares minted on deposit, tokens paid out) round down, amounts owed
+/// by a user (borrow shares minted, shares burned on withdrawal) round up, so rounding always favours the protocol.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    Down,
+    Up,
+}
+
+/// Unsigned fixed-point number with 18 decimal places
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Decimal(U192);
+
+impl Decimal {
+    pub fn zero() -> Self {
+        Self(U192::zero())
+    }
+
+    pub fn one() -> Self {
+        Self(U192::from(WAD))
+    }
+
+    /// Largest value that still fits in a `u128` when scaled, used as the health factor of a user without debt
+    pub fn max_value() -> Self {
+        Self(U192::from(u128::MAX))
+    }
+
+    /// Builds a decimal from a value expressed in basis points, e.g. 7_500 -> 0.75
+    pub fn from_bps(bps: u64) -> Self {
+        Self(U192::from(bps) * U192::from(WAD) / U192::from(BPS_SCALE))
+    }
+
+    /// Builds `numerator / denominator`
+    pub fn from_ratio(numerator: u64, denominator: u64, rounding: Rounding) -> Result<Self> {
+        Self::from(numerator).try_div(Self::from(denominator), rounding)
+    }
+
+    /// Builds a decimal from a value already scaled by `WAD`
+    pub fn from_scaled_val(value: u128) -> Self {
+        Self(U192::from(value))
+    }
+
+    /// Builds `10^exponent`
+    pub fn pow10(exponent: u32) -> Result<Self> {
+        let power = U192::from(10)
+            .checked_pow(U192::from(exponent))
+            .and_then(|power| power.checked_mul(U192::from(WAD)))
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(Self(power))
+    }
+
+    /// Returns the raw value scaled by `WAD`
+    pub fn to_scaled_val(self) -> Result<u128> {
+        u128::try_from(self.0).map_err(|_| ErrorCode::MathOverflow.into())
+    }
+
+    pub fn to_u64(self, rounding: Rounding) -> Result<u64> {
+        let wad = U192::from(WAD);
+        let value = match rounding {
+            Rounding::Down => self.0 / wad,
+            Rounding::Up => self.0.checked_add(wad - 1).ok_or(ErrorCode::MathOverflow)? / wad,
+        };
+        u64::try_from(value).map_err(|_| ErrorCode::MathOverflow.into())
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0.is_zero()
+    }
+
+    pub fn try_add(self, rhs: Self) -> Result<Self> {
+        Ok(Self(self.0.checked_add(rhs.0).ok_or(ErrorCode::MathOverflow)?))
+    }
+
+    pub fn try_sub(self, rhs: Self) -> Result<Self> {
+        Ok(Self(self.0.checked_sub(rhs.0).ok_or(ErrorCode::MathOverflow)?))
+    }
+
+    pub fn try_mul(self, rhs: Self, rounding: Rounding) -> Result<Self> {
+        let product = self.0.checked_mul(rhs.0).ok_or(ErrorCode::MathOverflow)?;
+        Ok(Self(div_round(product, U192::from(WAD), rounding)?))
+    }
+
+    pub fn try_div(self, rhs: Self, rounding: Rounding) -> Result<Self> {
+        require!(!rhs.is_zero(), ErrorCode::MathOverflow);
+        let scaled = self.0.checked_mul(U192::from(WAD)).ok_or(ErrorCode::MathOverflow)?;
+        Ok(Self(div_round(scaled, rhs.0, rounding)?))
+    }
+
+    pub fn min(self, rhs: Self) -> Self {
+        std::cmp::min(self, rhs)
+    }
+}
+
+impl From<u64> for Decimal {
+    fn from(value: u64) -> Self {
+        Self(U192::from(value) * U192::from(WAD))
+    }
+}
+
+fn div_round(numerator: U192, denominator: U192, rounding: Rounding) -> Result<U192> {
+    let quotient = numerator / denominator;
+    match rounding {
+        Rounding::Down => Ok(quotient),
+        Rounding::Up if (numerator % denominator).is_zero() => Ok(quotient),
+        Rounding::Up => quotient.checked_add(U192::one()).ok_or(ErrorCode::MathOverflow.into()),
+    }
+}
+
+/// Growth factor of a balance compounding every second at `rate_per_second` for `elapsed` seconds.
+///
+/// Uses the first terms of the binomial expansion of `(1 + r)^t`, which is deterministic, cheap in
+/// compute units and slightly undercharges compared to exact compounding.
+pub fn compound_interest(rate_per_second: Decimal, elapsed: u64) -> Result<Decimal> {
+    if elapsed == 0 || rate_per_second.is_zero() {
+        return Ok(Decimal::one());
+    }
+
+    let exp = U192::from(elapsed);
+    let exp_minus_one = exp - U192::one();
+    let exp_minus_two = exp.saturating_sub(U192::from(2));
+
+    // Each term is built from the previous one, so no intermediate rounds a per-second rate squared away
+    let wad = U192::from(WAD);
+    let rate = rate_per_second.0;
+    let mul = |a: U192, b: U192| a.checked_mul(b).ok_or(ErrorCode::MathOverflow);
+
+    let first_term = mul(rate, exp)?;
+    let second_term = mul(first_term, mul(rate, exp_minus_one)?)? / (wad * U192::from(2));
+    let third_term = mul(second_term, mul(rate, exp_minus_two)?)? / (wad * U192::from(3));
+
+    Decimal::one()
+        .try_add(Decimal(first_term))?
+        .try_add(Decimal(second_term))?
+        .try_add(Decimal(third_term))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::SECONDS_PER_YEAR;
+
+    fn overflow() -> Error {
+        ErrorCode::MathOverflow.into()
+    }
+
+    #[test]
+    fn try_mul_rounds_in_the_requested_direction() {
+        let third = Decimal::from_ratio(1, 3, Rounding::Down).unwrap();
+        let one_wei = Decimal::from_scaled_val(1);
+
+        assert_eq!(third.try_mul(one_wei, Rounding::Down).unwrap(), Decimal::zero());
+        assert_eq!(third.try_mul(one_wei, Rounding::Up).unwrap(), one_wei);
+        assert_eq!(
+            Decimal::from(3).try_mul(Decimal::from_bps(5_000), Rounding::Up).unwrap(),
+            Decimal::from_scaled_val(3 * WAD / 2),
+        );
+    }
+
+    #[test]
+    fn try_div_rounds_in_the_requested_direction() {
+        let down = Decimal::from(1).try_div(Decimal::from(3), Rounding::Down).unwrap();
+        let up = Decimal::from(1).try_div(Decimal::from(3), Rounding::Up).unwrap();
+
+        assert_eq!(down, Decimal::from_scaled_val(333_333_333_333_333_333));
+        assert_eq!(up, Decimal::from_scaled_val(333_333_333_333_333_334));
+        assert_eq!(Decimal::from(6).try_div(Decimal::from(3), Rounding::Up).unwrap(), Decimal::from(2));
+    }
+
+    #[test]
+    fn try_div_by_zero_is_an_overflow() {
+        assert_eq!(Decimal::from(1).try_div(Decimal::zero(), Rounding::Down).unwrap_err(), overflow());
+        assert_eq!(Decimal::from_ratio(1, 0, Rounding::Up).unwrap_err(), overflow());
+    }
+
+    #[test]
+    fn to_u64_rounds_in_the_requested_direction() {
+        let value = Decimal::from_scaled_val(2 * WAD + 1);
+
+        assert_eq!(value.to_u64(Rounding::Down).unwrap(), 2);
+        assert_eq!(value.to_u64(Rounding::Up).unwrap(), 3);
+        assert_eq!(Decimal::from(7).to_u64(Rounding::Up).unwrap(), 7);
+        assert_eq!(Decimal::from(u64::MAX).try_add(Decimal::one()).unwrap().to_u64(Rounding::Down).unwrap_err(), overflow());
+    }
+
+    #[test]
+    fn compound_interest_without_time_or_rate_is_one() {
+        assert_eq!(compound_interest(Decimal::from_bps(100), 0).unwrap(), Decimal::one());
+        assert_eq!(compound_interest(Decimal::zero(), 1_000).unwrap(), Decimal::one());
+    }
+
+    #[test]
+    fn compound_interest_over_one_second_adds_the_rate() {
+        let rate = Decimal::from_bps(100);
+        assert_eq!(compound_interest(rate, 1).unwrap(), Decimal::one().try_add(rate).unwrap());
+    }
+
+    #[test]
+    fn compound_interest_is_exact_up_to_three_periods() {
+        // 1.01^3 = 1.030301
+        let growth = compound_interest(Decimal::from_bps(100), 3).unwrap();
+        assert_eq!(growth, Decimal::from_scaled_val(1_030_301_000_000_000_000));
+    }
+
+    #[test]
+    fn compound_interest_over_a_year_slightly_undercharges() {
+        // 10% a year compounded every second is e^0.1 = 1.105170918..., the expansion stops at 1.1051666...
+        let rate = Decimal::from_bps(1_000)
+            .try_div(Decimal::from(SECONDS_PER_YEAR), Rounding::Down)
+            .unwrap();
+        let growth = compound_interest(rate, SECONDS_PER_YEAR).unwrap();
+
+        assert!(growth > Decimal::from_scaled_val(1_105_166_000_000_000_000));
+        assert!(growth < Decimal::from_scaled_val(1_105_167_000_000_000_000));
+    }
+}
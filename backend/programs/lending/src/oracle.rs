@@ -0,0 +1,172 @@
+use anchor_lang::prelude::*;
+use pyth_solana_receiver_sdk::price_update::{PriceUpdateV2, VerificationLevel};
+use switchboard_on_demand::{sb_pid, PullFeedAccountData};
+use crate::error::ErrorCode;
+use crate::math::{Decimal, Rounding};
+use crate::state::{Bank, OracleConfig, OracleType};
+
+/// Which of the oracle's prices to read
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum PriceKind {
+    /// Latest aggregate price
+    Spot,
+    /// Exponentially weighted moving average, slower to follow short-lived spikes. Only Pyth publishes one,
+    /// other oracles fall back to their spot price.
+    Ema,
+}
+
+/// Price of one base unit of a bank's asset in USD, read from any supported oracle
+#[derive(Clone, Copy)]
+pub struct OraclePrice {
+    pub price: Decimal,
+    pub confidence: Decimal,
+    /// Unix timestamp the oracle published the price at
+    pub publish_time: i64,
+}
+
+impl OraclePrice {
+    /// Bottom of the confidence band, used to value assets
+    pub fn lower(&self) -> Result<Decimal> {
+        self.price.try_sub(self.confidence)
+    }
+
+    /// Top of the confidence band, used to value liabilities
+    pub fn upper(&self) -> Result<Decimal> {
+        self.price.try_add(self.confidence)
+    }
+}
+
+/// Reads the USD price of one base unit of a bank's asset from its configured oracle accounts.
+///
+/// Prices are normalized by the feed's exponent and the mint's `decimals`, so multiplying them by raw token
+/// amounts gives USD values that can be compared across mints. Fixed prices ignore their price account.
+///
+/// Banks with a fallback oracle fall back to it while the primary price is stale. When both are fresh, they
+/// must agree within `max_oracle_deviation` and the primary price is used.
+pub fn load_price<'a>(
+    bank: &Bank,
+    price_account: &'a AccountInfo<'a>,
+    fallback_price_account: Option<&'a AccountInfo<'a>>,
+    kind: PriceKind,
+) -> Result<OraclePrice> {
+    let now = Clock::get()?.unix_timestamp;
+    let primary = read_price(&bank.oracle, price_account, kind, now)?;
+    let primary_fresh = is_fresh(&primary, &bank.oracle, now);
+
+    let Some(fallback_oracle) = &bank.fallback_oracle else {
+        require!(primary_fresh, ErrorCode::StalePrice);
+        return normalize_price(primary, bank.decimals, bank.oracle.max_confidence_bps);
+    };
+
+    let fallback_price_account = fallback_price_account.ok_or(ErrorCode::InvalidRemainingAccounts)?;
+    let fallback = read_price(fallback_oracle, fallback_price_account, kind, now)?;
+    let fallback_fresh = is_fresh(&fallback, fallback_oracle, now);
+
+    match (primary_fresh, fallback_fresh) {
+        (true, true) => {
+            check_deviation(&primary, &fallback, bank.max_oracle_deviation)?;
+            normalize_price(primary, bank.decimals, bank.oracle.max_confidence_bps)
+        }
+        (true, false) => normalize_price(primary, bank.decimals, bank.oracle.max_confidence_bps),
+        (false, true) => normalize_price(fallback, bank.decimals, fallback_oracle.max_confidence_bps),
+        (false, false) => Err(ErrorCode::OraclesStale.into()),
+    }
+}
+
+/// Reads a price from a single oracle without a fallback, such as an e-mode category's shared oracle
+pub fn load_oracle_price<'a>(oracle: &OracleConfig, decimals: u8, price_account: &'a AccountInfo<'a>, kind: PriceKind) -> Result<OraclePrice> {
+    let now = Clock::get()?.unix_timestamp;
+    let price = read_price(oracle, price_account, kind, now)?;
+    require!(is_fresh(&price, oracle, now), ErrorCode::StalePrice);
+    normalize_price(price, decimals, oracle.max_confidence_bps)
+}
+
+fn is_fresh(price: &OraclePrice, oracle: &OracleConfig, now: i64) -> bool {
+    now.saturating_sub(price.publish_time) <= oracle.max_staleness as i64
+}
+
+/// Fails if the two prices differ by more than `max_deviation` basis points of the primary price
+fn check_deviation(primary: &OraclePrice, fallback: &OraclePrice, max_deviation: u64) -> Result<()> {
+    let difference = if primary.price >= fallback.price {
+        primary.price.try_sub(fallback.price)?
+    } else {
+        fallback.price.try_sub(primary.price)?
+    };
+    let max_difference = primary.price.try_mul(Decimal::from_bps(max_deviation), Rounding::Down)?;
+    require!(difference <= max_difference, ErrorCode::OracleDeviationExceeded);
+    Ok(())
+}
+
+/// Reads the raw USD price of one whole token
+fn read_price<'a>(oracle: &OracleConfig, price_account: &'a AccountInfo<'a>, kind: PriceKind, now: i64) -> Result<OraclePrice> {
+    match oracle.oracle_type {
+        OracleType::PythPull => {
+            let price_update = Account::<PriceUpdateV2>::try_from(price_account)?;
+            require!(price_update.verification_level.gte(VerificationLevel::Full), ErrorCode::InvalidOracleAccount);
+            let price = price_update
+                .get_price_unchecked(&oracle.feed_id)
+                .map_err(|_| ErrorCode::InvalidOracleAccount)?;
+
+            let (value, conf) = match kind {
+                PriceKind::Spot => (price.price, price.conf),
+                PriceKind::Ema => (price_update.price_message.ema_price, price_update.price_message.ema_conf),
+            };
+            require!(value > 0, ErrorCode::InvalidPrice);
+
+            Ok(OraclePrice {
+                price: scale_by_pow10(Decimal::from(value as u64), price.exponent, Rounding::Down)?,
+                confidence: scale_by_pow10(Decimal::from(conf), price.exponent, Rounding::Up)?,
+                publish_time: price.publish_time,
+            })
+        }
+        OracleType::SwitchboardOnDemand => {
+            // The feed is identified by its account address rather than an ID inside the account
+            require_keys_eq!(price_account.key(), Pubkey::new_from_array(oracle.feed_id), ErrorCode::InvalidOracleAccount);
+            require_keys_eq!(*price_account.owner, sb_pid(), ErrorCode::InvalidOracleAccount);
+
+            let feed = PullFeedAccountData::parse(price_account.data.borrow())
+                .map_err(|_| ErrorCode::InvalidOracleAccount)?;
+            require!(feed.result.slot != 0 && feed.result.value > 0, ErrorCode::InvalidPrice);
+
+            // Switchboard results carry 18 decimals, the same scale as `Decimal`
+            Ok(OraclePrice {
+                price: Decimal::from_scaled_val(feed.result.value as u128),
+                confidence: Decimal::from_scaled_val(feed.result.std_dev.max(0) as u128),
+                publish_time: feed.last_update_timestamp,
+            })
+        }
+        OracleType::Fixed => Ok(OraclePrice {
+            price: Decimal::from_scaled_val(oracle.fixed_price),
+            confidence: Decimal::zero(),
+            publish_time: now,
+        }),
+    }
+}
+
+/// Checks the confidence of a whole-token price and converts it into a price per base unit
+fn normalize_price(price: OraclePrice, decimals: u8, max_confidence_bps: u64) -> Result<OraclePrice> {
+    require!(!price.price.is_zero(), ErrorCode::InvalidPrice);
+
+    let max_confidence = price.price.try_mul(Decimal::from_bps(max_confidence_bps), Rounding::Down)?;
+    require!(price.confidence <= max_confidence, ErrorCode::PriceConfidenceTooWide);
+
+    // The band is widened rather than narrowed by rounding
+    let scale = -(decimals as i32);
+    let normalized = OraclePrice {
+        price: scale_by_pow10(price.price, scale, Rounding::Down)?,
+        confidence: scale_by_pow10(price.confidence, scale, Rounding::Up)?,
+        publish_time: price.publish_time,
+    };
+
+    // A low priced mint with many decimals can round down to nothing per base unit, which would value its debt at 0
+    require!(!normalized.price.is_zero(), ErrorCode::InvalidPrice);
+    Ok(normalized)
+}
+
+fn scale_by_pow10(value: Decimal, scale: i32, rounding: Rounding) -> Result<Decimal> {
+    if scale >= 0 {
+        value.try_mul(Decimal::pow10(scale as u32)?, rounding)
+    } else {
+        value.try_div(Decimal::pow10(scale.unsigned_abs())?, rounding)
+    }
+}
@@ -1,12 +1,113 @@
 use anchor_lang::prelude::*;
+use crate::constants::{MAX_ALLOWED_MINT_EXTENSIONS, MAX_EMODE_CATEGORIES, MAX_USER_POSITIONS, VIRTUAL_ASSETS, VIRTUAL_SHARES};
+use crate::error::ErrorCode;
+use crate::math::{Decimal, Rounding};
 
+/// Singleton holding the protocol-wide roles
 #[account]
 #[derive(InitSpace)]
+pub struct ProtocolConfig {
+    /// Can list banks and change any bank's configuration
+    pub admin: Pubkey,
+    /// Admin nominated by `propose_admin`, takes over once they call `accept_admin`
+    pub pending_admin: Pubkey,
+    /// Pause authority, expected to be a hot key that can react quickly
+    pub guardian: Pubkey,
+    /// Wallet that receives protocol fees
+    pub fee_recipient: Pubkey,
+    /// Operations halted across every bank
+    pub paused: PauseFlags,
+    /// Token-2022 mint extensions, as `ExtensionType` values, that a bank's mint may have
+    #[max_len(MAX_ALLOWED_MINT_EXTENSIONS)]
+    pub allowed_mint_extensions: Vec<u16>,
+    /// Risk parameters for groups of correlated assets, see `EModeCategory`
+    #[max_len(MAX_EMODE_CATEGORIES)]
+    pub emode_categories: Vec<EModeCategory>,
+    pub bump: u8,
+}
+
+impl ProtocolConfig {
+    /// Fails if `operation` is paused protocol-wide or on any of the given banks
+    pub fn require_not_paused(&self, banks: &[&Bank], operation: Operation) -> Result<()> {
+        require!(!self.paused.is_paused(operation), ErrorCode::OperationPaused);
+        for bank in banks {
+            require!(!bank.paused.is_paused(operation), ErrorCode::OperationPaused);
+        }
+        Ok(())
+    }
+
+    /// E-mode category with the given ID, `None` for category 0 which means no e-mode
+    pub fn emode_category(&self, id: u8) -> Option<&EModeCategory> {
+        if id == 0 {
+            return None;
+        }
+        self.emode_categories.iter().find(|category| category.id == id)
+    }
+}
+
+/// Efficiency mode for correlated assets such as stablecoins or SOL and its liquid staking tokens.
+///
+/// A user who opts into a category and only borrows assets of that category gets the category's risk
+/// parameters, and optionally its shared price, for their positions in banks of the category.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct EModeCategory {
+    /// Identifier referenced by `Bank::emode_category` and `User::emode_category`, never 0
+    pub id: u8,
+    /// Max percentage of collateral that can be borrowed
+    pub max_ltv: u64,
+    /// LTV at which the loan is defined as under collateralized and can be liquidated
+    pub liquidation_threshold: u64,
+    /// Maximum bonus paid to liquidators, replaces the bank's `liquidation_bonus`
+    pub liquidation_bonus: u64,
+    /// Price feed shared by every asset of the category instead of each bank's own oracle
+    pub oracle: Option<OracleConfig>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Deposit,
+    Withdraw,
+    Borrow,
+    Repay,
+    Liquidate,
+    FlashLoan,
+}
+
+/// Per-operation circuit breakers. Each flag is independent, so pausing borrows during an incident
+/// still lets users repay and keepers liquidate unless those are paused explicitly.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct PauseFlags {
+    pub deposit: bool,
+    pub withdraw: bool,
+    pub borrow: bool,
+    pub repay: bool,
+    pub liquidate: bool,
+    pub flash_loan: bool,
+}
+
+impl PauseFlags {
+    pub fn is_paused(&self, operation: Operation) -> bool {
+        match operation {
+            Operation::Deposit => self.deposit,
+            Operation::Withdraw => self.withdraw,
+            Operation::Borrow => self.borrow,
+            Operation::Repay => self.repay,
+            Operation::Liquidate => self.liquidate,
+            Operation::FlashLoan => self.flash_loan,
+        }
+    }
+}
+
+#[account]
+#[derive(InitSpace, Default)]
 pub struct Bank {
-    /// Authority to make changes to Bank State
+    /// Admin that listed the bank. Bank updates are authorized by the current protocol admin, so this key
+    /// keeps no rights after an admin transfer.
     pub authority: Pubkey,
     /// Mint address of the asset 
     pub mint_address: Pubkey,
+    /// Decimals of the asset's mint, used to normalize oracle prices
+    pub decimals: u8,
     /// Current number of tokens in the bank
     pub total_deposits: u64,
     /// Current number of deposit shares in the bank
@@ -17,44 +118,346 @@ pub struct Bank {
     pub total_borrowed_shares: u64,
     /// LTV at which the loan is defined as under collateralized and can be liquidated 
     pub liquidation_threshold: u64,
-    /// Bonus percentage of collateral that can be liquidated
+    /// Maximum bonus paid to liquidators, reached at deep shortfalls or after `liquidation_bonus_duration`
     pub liquidation_bonus: u64,
+    /// Bonus paid to liquidators of a barely unhealthy position
+    pub min_liquidation_bonus: u64,
+    /// Seconds for the bonus to grow from the minimum to the maximum once a user is liquidatable, 0 disables it
+    pub liquidation_bonus_duration: u64,
     /// Percentage of collateral that can be liquidated
     pub liquidation_close_factor: u64,
     /// Max percentage of collateral that can be borrowed
     pub max_ltv: u64,
-    /// Last updated timestamp
+    /// Maximum total deposits in tokens, 0 disables the cap
+    pub deposit_cap: u64,
+    /// Maximum total borrows in tokens, 0 disables the cap
+    pub borrow_cap: u64,
+    /// Fee charged on flash loans
+    pub flash_loan_fee: u64,
+    /// Fee added to the debt of every new borrow
+    pub origination_fee: u64,
+    /// Protocol's cut of the liquidation bonus paid out of this bank's collateral
+    pub liquidation_protocol_fee: u64,
+    /// Collateral in this bank cannot be combined with other collateral and only backs debt up to `debt_ceiling`
+    pub isolated: bool,
+    /// Maximum debt in USD backed by this bank's collateral when isolated, see `DEBT_CEILING_DECIMALS`
+    pub debt_ceiling: u64,
+    /// Can be borrowed by users whose collateral is an isolated asset
+    pub borrowable_in_isolation: bool,
+    /// E-mode category of the bank's asset, 0 when it does not belong to one
+    pub emode_category: u8,
+    /// Amount lent out by the flash loan in progress, 0 when there is none
+    pub flash_loan_amount: u64,
+    /// Fees owed to the protocol, held in the treasury but not part of `total_deposits`
+    pub protocol_fees: u64,
+    /// Debt in USD currently backed by this bank's collateral when isolated, see `DEBT_CEILING_DECIMALS`
+    pub isolated_debt: u64,
+    /// Last time interest was accrued
     pub last_updated: i64,
-    pub interest_rate: u64,
+    /// Parameters of the utilization-based borrow rate curve
+    pub interest_rate: InterestRateConfig,
+    /// Price feed used to value the bank's asset
+    pub oracle: OracleConfig,
+    /// Price feed used while the primary oracle is stale
+    pub fallback_oracle: Option<OracleConfig>,
+    /// Maximum difference between the primary and fallback prices, in basis points of the primary price
+    pub max_oracle_deviation: u64,
+    /// Operations halted on this bank only
+    pub paused: PauseFlags,
+}
+
+/// Risk and interest parameters of a bank that its authority can change after listing.
+/// Percentages are in basis points.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct BankConfig {
+    /// LTV at which the loan is defined as under collateralized and can be liquidated
+    pub liquidation_threshold: u64,
+    /// Maximum bonus paid to liquidators, reached at deep shortfalls or after `liquidation_bonus_duration`
+    pub liquidation_bonus: u64,
+    /// Bonus paid to liquidators of a barely unhealthy position
+    pub min_liquidation_bonus: u64,
+    /// Seconds for the bonus to grow from the minimum to the maximum once a user is liquidatable, 0 disables it
+    pub liquidation_bonus_duration: u64,
+    /// Percentage of collateral that can be liquidated
+    pub liquidation_close_factor: u64,
+    /// Max percentage of collateral that can be borrowed
+    pub max_ltv: u64,
+    /// Maximum total deposits in tokens, 0 disables the cap
+    pub deposit_cap: u64,
+    /// Maximum total borrows in tokens, 0 disables the cap
+    pub borrow_cap: u64,
+    /// Fee charged on flash loans
+    pub flash_loan_fee: u64,
+    /// Fee added to the debt of every new borrow
+    pub origination_fee: u64,
+    /// Protocol's cut of the liquidation bonus paid out of this bank's collateral
+    pub liquidation_protocol_fee: u64,
+    /// Collateral in this bank cannot be combined with other collateral and only backs debt up to `debt_ceiling`
+    pub isolated: bool,
+    /// Maximum debt in USD backed by this bank's collateral when isolated, see `DEBT_CEILING_DECIMALS`
+    pub debt_ceiling: u64,
+    /// Can be borrowed by users whose collateral is an isolated asset
+    pub borrowable_in_isolation: bool,
+    /// E-mode category of the bank's asset, 0 when it does not belong to one
+    pub emode_category: u8,
+    /// Parameters of the utilization-based borrow rate curve
+    pub interest_rate: InterestRateConfig,
+}
+
+// Shares are priced at (total assets + VIRTUAL_ASSETS) / (total shares + VIRTUAL_SHARES). The virtual
+// offset makes inflating the share price of an empty bank cost far more than a first-depositor attack can steal.
+impl Bank {
+    pub fn config(&self) -> BankConfig {
+        BankConfig {
+            liquidation_threshold: self.liquidation_threshold,
+            liquidation_bonus: self.liquidation_bonus,
+            min_liquidation_bonus: self.min_liquidation_bonus,
+            liquidation_bonus_duration: self.liquidation_bonus_duration,
+            liquidation_close_factor: self.liquidation_close_factor,
+            max_ltv: self.max_ltv,
+            deposit_cap: self.deposit_cap,
+            borrow_cap: self.borrow_cap,
+            flash_loan_fee: self.flash_loan_fee,
+            origination_fee: self.origination_fee,
+            liquidation_protocol_fee: self.liquidation_protocol_fee,
+            isolated: self.isolated,
+            debt_ceiling: self.debt_ceiling,
+            borrowable_in_isolation: self.borrowable_in_isolation,
+            emode_category: self.emode_category,
+            interest_rate: self.interest_rate,
+        }
+    }
+
+    pub fn set_config(&mut self, config: &BankConfig) {
+        self.liquidation_threshold = config.liquidation_threshold;
+        self.liquidation_bonus = config.liquidation_bonus;
+        self.min_liquidation_bonus = config.min_liquidation_bonus;
+        self.liquidation_bonus_duration = config.liquidation_bonus_duration;
+        self.liquidation_close_factor = config.liquidation_close_factor;
+        self.max_ltv = config.max_ltv;
+        self.deposit_cap = config.deposit_cap;
+        self.borrow_cap = config.borrow_cap;
+        self.flash_loan_fee = config.flash_loan_fee;
+        self.origination_fee = config.origination_fee;
+        self.liquidation_protocol_fee = config.liquidation_protocol_fee;
+        self.isolated = config.isolated;
+        self.debt_ceiling = config.debt_ceiling;
+        self.borrowable_in_isolation = config.borrowable_in_isolation;
+        self.emode_category = config.emode_category;
+        self.interest_rate = config.interest_rate;
+    }
+
+    /// Deposit shares worth `amount` tokens
+    pub fn deposit_shares(&self, amount: u64, rounding: Rounding) -> Result<u64> {
+        to_shares(amount, self.total_deposits, self.total_deposit_shares, rounding)
+    }
+
+    /// Tokens that `shares` deposit shares can be redeemed for
+    pub fn deposit_amount(&self, shares: u64, rounding: Rounding) -> Result<u64> {
+        to_amount(shares, self.total_deposits, self.total_deposit_shares, rounding)
+    }
+
+    /// Borrow shares representing a debt of `amount` tokens
+    pub fn borrow_shares(&self, amount: u64, rounding: Rounding) -> Result<u64> {
+        to_shares(amount, self.total_borrowed, self.total_borrowed_shares, rounding)
+    }
+
+    /// Tokens owed for `shares` borrow shares
+    pub fn borrow_amount(&self, shares: u64, rounding: Rounding) -> Result<u64> {
+        to_amount(shares, self.total_borrowed, self.total_borrowed_shares, rounding)
+    }
+
+    pub fn add_isolated_debt(&mut self, debt: u64) -> Result<()> {
+        let isolated_debt = self.isolated_debt.checked_add(debt).ok_or(ErrorCode::MathOverflow)?;
+        require!(isolated_debt <= self.debt_ceiling, ErrorCode::DebtCeilingExceeded);
+        self.isolated_debt = isolated_debt;
+        Ok(())
+    }
+
+    pub fn remove_isolated_debt(&mut self, debt: u64) {
+        self.isolated_debt = self.isolated_debt.saturating_sub(debt);
+    }
+}
+
+fn to_shares(amount: u64, total_assets: u64, total_shares: u64, rounding: Rounding) -> Result<u64> {
+    let total_assets = Decimal::from(total_assets).try_add(Decimal::from(VIRTUAL_ASSETS))?;
+    let total_shares = Decimal::from(total_shares).try_add(Decimal::from(VIRTUAL_SHARES))?;
+    Decimal::from(amount)
+        .try_mul(total_shares, rounding)?
+        .try_div(total_assets, rounding)?
+        .to_u64(rounding)
+}
+
+fn to_amount(shares: u64, total_assets: u64, total_shares: u64, rounding: Rounding) -> Result<u64> {
+    let total_assets = Decimal::from(total_assets).try_add(Decimal::from(VIRTUAL_ASSETS))?;
+    let total_shares = Decimal::from(total_shares).try_add(Decimal::from(VIRTUAL_SHARES))?;
+    Decimal::from(shares)
+        .try_mul(total_assets, rounding)?
+        .try_div(total_shares, rounding)?
+        .to_u64(rounding)
+}
+
+/// Kinked rate model: the borrow rate grows along `slope1` up to `optimal_utilization`
+/// and along the much steeper `slope2` above it. All values are in basis points, rates are annual.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct InterestRateConfig {
+    /// Borrow rate at 0% utilization
+    pub base_rate: u64,
+    /// Rate increase between 0% and optimal utilization
+    pub slope1: u64,
+    /// Rate increase between optimal and 100% utilization
+    pub slope2: u64,
+    /// Utilization at which the curve kinks
+    pub optimal_utilization: u64,
+    /// Share of borrower interest withheld from depositors
+    pub reserve_factor: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Default, InitSpace)]
+pub enum OracleType {
+    /// Pyth pull oracle, read from a `PriceUpdateV2` account
+    #[default]
+    PythPull,
+    /// Switchboard On-Demand pull feed account
+    SwitchboardOnDemand,
+    /// Constant price set in the oracle config, for stablecoins
+    Fixed,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct OracleConfig {
+    /// Kind of oracle account the price is read from
+    pub oracle_type: OracleType,
+    /// Feed ID of the asset's USD price feed for Pyth, address of the pull feed account for Switchboard
+    pub feed_id: [u8; 32],
+    /// Maximum age of a price in seconds before it is considered stale
+    pub max_staleness: u64,
+    /// Maximum confidence interval, in basis points of the price, before a price is rejected
+    pub max_confidence_bps: u64,
+    /// Price new borrows and withdrawals with the EMA instead of the spot price. Liquidations always use spot.
+    pub use_ema: bool,
+    /// USD price of one whole token scaled by `WAD`, only used by `OracleType::Fixed`
+    pub fixed_price: u128,
 }
 
-// Challenge: How would you update the user state to save "all_deposited_assets" and "all_borrowed_assets" to accommodate for several asset listings?  
 #[account]
 #[derive(InitSpace)]
 pub struct User {
     /// Pubkey of the user's wallet 
     pub owner: Pubkey,
-    /// User's deposited tokens in the SOL bank
-    pub deposited_sol: u64,
-    /// User's deposited shares in the SOL bank 
-    pub deposited_sol_shares: u64,
-    /// User's borrowed tokens in the SOL bank
-    pub borrowed_sol: u64,
-    /// User's borrowed shares in the SOL bank
-    pub borrowed_sol_shares: u64, 
-    /// User's deposited tokens in the USDC bank
-    pub deposited_usdc: u64,
-    /// User's deposited shares in the USDC bank 
-    pub deposited_usdc_shares: u64, 
-    /// User's borrowed tokens in the USDC bank
-    pub borrowed_usdc: u64,
-    /// User's borrowed shares in the USDC bank
-    pub borrowed_usdc_shares: u64, 
-    /// USDC mint address
-    pub usdc_address: Pubkey,
-    /// Current health factor of the user
-    pub health_factor: u64,
+    /// User's deposit and borrow positions, one slot per bank
+    #[max_len(MAX_USER_POSITIONS)]
+    pub positions: Vec<Position>,
+    /// Isolated bank the user last enabled as collateral, see `User::isolated_bank`. Positions' `isolated_debt`
+    /// counts against this bank's debt ceiling until it is repaid.
+    pub isolated_bank: Pubkey,
+    /// E-mode category the user opted into, 0 for none
+    pub emode_category: u8,
+    /// Health factor of the user at the last health check, scaled by `WAD`
+    pub health_factor: u128,
+    /// When a liquidation or `update_user_health` first found the user unhealthy, 0 once a health check finds
+    /// them healthy again
+    pub liquidatable_since: i64,
     /// Last updated timestamp
     pub last_updated: i64,
 }
 
+// Token amounts are derived from shares at the bank's current exchange rate, see `Bank::deposit_amount`
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Default, InitSpace)]
+pub struct Position {
+    /// Bank the position belongs to
+    pub bank: Pubkey,
+    /// Mint address of the bank's asset
+    pub mint: Pubkey,
+    /// User's deposited shares in the bank
+    pub deposited_shares: u64,
+    /// User's borrowed shares in the bank
+    pub borrowed_shares: u64,
+    /// Whether the deposit backs the user's debt and can be seized in a liquidation
+    pub is_collateral: bool,
+    /// Part of the isolated bank's `isolated_debt` added by this position's borrows, see `DEBT_CEILING_DECIMALS`
+    pub isolated_debt: u64,
+}
+
+impl Position {
+    pub fn is_empty(&self) -> bool {
+        self.deposited_shares == 0 && self.borrowed_shares == 0
+    }
+
+    /// Releases the part of `isolated_debt` backing `shares` of the position's debt, call before burning them.
+    /// Debt is released at the value it was counted at when borrowed, so the ceiling does not drift with prices.
+    pub fn release_isolated_debt(&mut self, shares: u64) -> Result<u64> {
+        let released = if shares >= self.borrowed_shares {
+            self.isolated_debt
+        } else {
+            Decimal::from(self.isolated_debt)
+                .try_mul(Decimal::from_ratio(shares, self.borrowed_shares, Rounding::Down)?, Rounding::Down)?
+                .to_u64(Rounding::Down)?
+        };
+        self.isolated_debt -= released;
+        Ok(released)
+    }
+}
+
+impl User {
+    pub fn position(&self, bank: &Pubkey) -> Option<&Position> {
+        self.positions.iter().find(|position| position.bank == *bank)
+    }
+
+    pub fn position_mut(&mut self, bank: &Pubkey) -> Result<&mut Position> {
+        self.positions
+            .iter_mut()
+            .find(|position| position.bank == *bank)
+            .ok_or_else(|| ErrorCode::PositionNotFound.into())
+    }
+
+    /// Isolated bank backing the user's debt, if their collateral is an isolated asset.
+    /// Isolation mode ends once that deposit is withdrawn, seized or no longer used as collateral.
+    pub fn isolated_bank(&self) -> Option<Pubkey> {
+        self.position(&self.isolated_bank)
+            .filter(|position| position.is_collateral && position.deposited_shares > 0)
+            .map(|position| position.bank)
+    }
+
+    /// Whether a deposit in `bank` can be used as collateral next to the user's other collateral
+    pub fn can_use_as_collateral(&self, bank: &Pubkey, isolated: bool) -> bool {
+        if isolated {
+            // Debt still counted against another isolated bank's ceiling keeps that bank as the user's isolated bank
+            let other_isolated_debt = self.isolated_bank != *bank && self.positions.iter().any(|position| position.isolated_debt > 0);
+            !other_isolated_debt && !self.positions.iter().any(|position| {
+                position.bank != *bank && position.is_collateral && position.deposited_shares > 0
+            })
+        } else {
+            match self.isolated_bank() {
+                Some(isolated_bank) => isolated_bank == *bank,
+                None => true,
+            }
+        }
+    }
+
+    /// Returns the user's position in `bank`, opening a new slot if none exists yet
+    pub fn position_mut_or_open(&mut self, bank: &Pubkey, mint: &Pubkey) -> Result<&mut Position> {
+        if let Some(index) = self.positions.iter().position(|position| position.bank == *bank) {
+            return Ok(&mut self.positions[index]);
+        }
+
+        // Reuse a slot that has been fully repaid and withdrawn before growing the list
+        let index = match self.positions.iter().position(Position::is_empty) {
+            Some(index) => index,
+            None => {
+                require!(self.positions.len() < MAX_USER_POSITIONS, ErrorCode::MaxPositionsReached);
+                self.positions.push(Position::default());
+                self.positions.len() - 1
+            }
+        };
+
+        let position = &mut self.positions[index];
+        *position = Position {
+            bank: *bank,
+            mint: *mint,
+            is_collateral: true,
+            ..Position::default()
+        };
+        Ok(position)
+    }
+}
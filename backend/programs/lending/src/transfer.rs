@@ -0,0 +1,31 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{ self, Mint, TokenAccount, TokenInterface, TransferChecked };
+use crate::error::ErrorCode;
+
+/// Transfers `amount` from `from` into a bank's `treasury` and returns the amount the treasury received.
+///
+/// Token-2022 transfer fees are withheld from what the sender sends, so callers credit the returned amount
+/// rather than `amount`.
+pub fn transfer_in<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    mint: &InterfaceAccount<'info, Mint>,
+    from: &InterfaceAccount<'info, TokenAccount>,
+    treasury: &mut InterfaceAccount<'info, TokenAccount>,
+    authority: &Signer<'info>,
+    amount: u64,
+) -> Result<u64> {
+    let balance_before = treasury.amount;
+
+    let transfer_cpi_accounts = TransferChecked {
+        from: from.to_account_info(),
+        mint: mint.to_account_info(),
+        to: treasury.to_account_info(),
+        authority: authority.to_account_info(),
+    };
+
+    let cpi_ctx = CpiContext::new(token_program.to_account_info(), transfer_cpi_accounts);
+    token_interface::transfer_checked(cpi_ctx, amount, mint.decimals)?;
+
+    treasury.reload()?;
+    treasury.amount.checked_sub(balance_before).ok_or_else(|| ErrorCode::MathOverflow.into())
+}